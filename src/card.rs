@@ -0,0 +1,195 @@
+use crate::export::escape_html;
+use crate::history::Settings;
+use crate::thok::Thok;
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 420.0;
+/// the wpm chart, as (x, y, width, height)
+const CHART: (f64, f64, f64, f64) = (380.0, 110.0, 380.0, 220.0);
+
+/// What the test was, e.g. "words · english · 15 words · seed 42".
+fn describe(thok: &Thok, settings: &Settings) -> String {
+    let mut parts = vec![];
+    if let Some(mode) = &settings.mode {
+        parts.push(mode.name().to_string());
+    }
+    if let Some(language) = &settings.language {
+        parts.push(language.clone());
+    }
+    parts.push(match thok.number_of_secs {
+        Some(secs) => format!("{}s", secs),
+        None => format!("{} words", thok.number_of_words),
+    });
+    if let Some(seed) = settings.seed {
+        parts.push(format!("seed {}", seed));
+    }
+    if let Some(pace) = settings.pace {
+        parts.push(format!("pace {}", pace));
+    }
+    parts.join(" · ")
+}
+
+/// The wpm over time, scaled into `CHART` like the chart on the results
+/// screen: seconds from 1 across, wpm from 0 up.
+fn chart(wpm_coords: &[(f64, f64)]) -> String {
+    let (x, y, width, height) = CHART;
+    let duration = wpm_coords.last().map_or(1.0, |c| c.0).max(1.0);
+    let highest = wpm_coords.iter().map(|c| c.1).fold(0.0, f64::max).max(1.0);
+
+    let points = wpm_coords
+        .iter()
+        .map(|&(secs, wpm)| {
+            let across = if duration > 1.0 {
+                (secs - 1.0).max(0.0) / (duration - 1.0)
+            } else {
+                1.0
+            };
+            format!(
+                "{:.1},{:.1}",
+                x + across * width,
+                y + height - wpm / highest * height
+            )
+        })
+        .collect::<Vec<String>>();
+
+    let mut svg = format!(
+        "<path d=\"M{x} {y} V{bottom} H{right}\" fill=\"none\" stroke=\"#6c7086\"/>\n\
+         <text x=\"{label_x}\" y=\"{top_label}\" class=\"dim\" text-anchor=\"end\">{highest:.0}</text>\n\
+         <text x=\"{label_x}\" y=\"{bottom}\" class=\"dim\" text-anchor=\"end\">0</text>\n\
+         <text x=\"{right}\" y=\"{below}\" class=\"dim\" text-anchor=\"end\">{duration:.2}s</text>\n",
+        bottom = y + height,
+        right = x + width,
+        label_x = x - 8.0,
+        top_label = y + 12.0,
+        below = y + height + 22.0,
+    );
+    match points.as_slice() {
+        [] => {}
+        [point] => {
+            let (cx, cy) = point.split_once(',').unwrap();
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"#f5c2e7\"/>\n",
+                cx, cy
+            ));
+        }
+        _ => svg.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#f5c2e7\" stroke-width=\"3\" \
+             stroke-linejoin=\"round\"/>\n",
+            points.join(" ")
+        )),
+    }
+    svg
+}
+
+/// A shareable card of a finished test: its stats, how it was set up, when
+/// it finished and its wpm chart, as an SVG image.
+pub fn svg(thok: &Thok, settings: &Settings) -> String {
+    let date = thok.finished_at().unwrap_or_else(Local::now);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         viewBox=\"0 0 {WIDTH} {HEIGHT}\">\n\
+         <style>\n\
+         text {{ font-family: ui-monospace, Menlo, Consolas, monospace; fill: #cdd6f4; }}\n\
+         .dim {{ fill: #7f849c; font-size: 14px; }}\n\
+         </style>\n\
+         <rect width=\"{WIDTH}\" height=\"{HEIGHT}\" rx=\"16\" fill=\"#1e1e2e\"/>\n\
+         <text x=\"40\" y=\"60\" font-size=\"28\" font-weight=\"bold\">thokr</text>\n\
+         <text x=\"{right}\" y=\"60\" class=\"dim\" text-anchor=\"end\">{date}</text>\n\
+         <text x=\"40\" y=\"190\" font-size=\"96\" font-weight=\"bold\" \
+         style=\"fill: #a6e3a1\">{wpm}</text>\n\
+         <text x=\"40\" y=\"230\" font-size=\"24\">wpm</text>\n\
+         <text x=\"40\" y=\"290\" font-size=\"22\">{accuracy}% acc   {std_dev:.2} sd</text>\n\
+         {chart}\
+         <text x=\"40\" y=\"{footer}\" class=\"dim\">{description}</text>\n\
+         </svg>\n",
+        right = WIDTH - 40.0,
+        date = date.format("%Y-%m-%d %H:%M"),
+        wpm = thok.wpm,
+        accuracy = thok.accuracy,
+        std_dev = thok.std_dev,
+        chart = chart(&thok.wpm_coords),
+        footer = HEIGHT - 40.0,
+        description = escape_html(&describe(thok, settings)),
+    )
+}
+
+/// Writes the card of `thok` into `dir`, named after when the test finished;
+/// returns where it went. Existing cards are never overwritten: a second card
+/// of the same second gets a "-2" suffix, and so on.
+pub fn save(dir: &Path, thok: &Thok, settings: &Settings) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let date = thok.finished_at().unwrap_or_else(Local::now);
+    let name = format!("thokr-{}", date.format("%Y%m%d-%H%M%S"));
+    for n in 1.. {
+        let path = match n {
+            1 => dir.join(format!("{}.svg", name)),
+            n => dir.join(format!("{}-{}.svg", name, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(svg(thok, settings).as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Mode;
+    use chrono::TimeZone;
+
+    fn finished(prompt: &str) -> Thok {
+        let mut thok = Thok::new(prompt.to_string(), 2, None);
+        for c in prompt.chars() {
+            thok.write(c);
+        }
+        thok.finished_at = Some(Local.with_ymd_and_hms(2022, 5, 1, 9, 30, 0).unwrap());
+        thok.calc_results();
+        thok.wpm_coords = vec![(1.0, 50.0), (2.0, 60.0), (3.0, 55.0)];
+        thok.wpm = 55.0;
+        thok
+    }
+
+    #[test]
+    fn card_shows_the_results() {
+        let settings = Settings {
+            mode: Some(Mode::Words),
+            language: Some(String::from("<english>")),
+            seed: Some(42),
+            ..Settings::default()
+        };
+        let svg = svg(&finished("ab cd"), &settings);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(">55</text>"));
+        assert!(svg.contains(">100% acc"));
+        assert!(svg.contains(">2022-05-01 09:30</text>"));
+        assert!(svg.contains(">words · &lt;english&gt; · 2 words · seed 42</text>"));
+        // 1s..3s across the chart, 0..60 wpm up it
+        assert!(svg.contains("points=\"380.0,146.7 570.0,110.0 760.0,128.3\""));
+    }
+
+    #[test]
+    fn cards_are_named_after_the_finish() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().join("cards");
+        let thok = finished("ab");
+        let path = save(&dir, &thok, &Settings::default()).unwrap();
+        assert_eq!(path, dir.join("thokr-20220501-093000.svg"));
+        assert!(fs::read_to_string(&path).unwrap().contains("<polyline"));
+
+        // a second card of the same second doesn't replace the first
+        let again = save(&dir, &thok, &Settings::default()).unwrap();
+        assert_eq!(again, dir.join("thokr-20220501-093000-2.svg"));
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::history;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// number of words in the daily prompt
+pub const DAILY_WORDS: usize = 25;
+
+/// language the daily prompt is drawn from
+pub const DAILY_LANGUAGE: &str = "english1k";
+
+/// The calendar day of the current challenge. UTC so that everyone gets the
+/// same prompt regardless of their timezone.
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// Seed for the day's prompt, e.g. 2022-05-01 => 20220501.
+pub fn seed_for(date: NaiveDate) -> u64 {
+    date.year() as u64 * 10_000 + date.month() as u64 * 100 + date.day() as u64
+}
+
+/// One completed daily challenge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DailyRecord {
+    pub date: NaiveDate,
+    pub wpm: f64,
+    pub accuracy: f64,
+}
+
+/// What the results screen shows after a daily run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub best_wpm: f64,
+    pub best_accuracy: f64,
+    pub streak: usize,
+}
+
+const DAILY_FILE: &str = "daily.csv";
+
+/// Every daily challenge recorded in `dir` so far; malformed rows, and ones
+/// with a non-finite wpm or accuracy, are skipped.
+pub fn load(dir: &Path) -> Vec<DailyRecord> {
+    std::fs::read_to_string(dir.join(DAILY_FILE))
+        .map(|contents| parse(&contents))
+        .unwrap_or_default()
+}
+
+fn parse(contents: &str) -> Vec<DailyRecord> {
+    contents
+        .lines()
+        .skip(1) // header
+        .filter_map(|line| {
+            let mut fields = line.split(',');
+            let record = DailyRecord {
+                date: NaiveDate::parse_from_str(fields.next()?, "%Y-%m-%d").ok()?,
+                wpm: fields.next()?.parse().ok()?,
+                accuracy: fields.next()?.parse().ok()?,
+            };
+            (record.wpm.is_finite() && record.accuracy.is_finite()).then_some(record)
+        })
+        .collect()
+}
+
+/// Appends `record` to the daily log in `dir`, holding the same lock as
+/// `history::append`.
+pub fn save(dir: &Path, record: &DailyRecord) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let _lock = history::lock(dir)?;
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(dir.join(DAILY_FILE))?;
+
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "date,wpm,accuracy")?;
+    }
+
+    writeln!(
+        file,
+        "{},{},{}",
+        record.date.format("%Y-%m-%d"),
+        record.wpm,
+        record.accuracy
+    )
+}
+
+/// Whether the challenge for `date` has been completed at least once.
+pub fn completed_on(records: &[DailyRecord], date: NaiveDate) -> bool {
+    records.iter().any(|r| r.date == date)
+}
+
+/// Number of consecutive days with a completed challenge, ending on `date`
+/// (or the day before, so an unfinished today doesn't break the streak).
+pub fn streak(records: &[DailyRecord], date: NaiveDate) -> usize {
+    let mut day = if completed_on(records, date) {
+        date
+    } else {
+        date - Duration::days(1)
+    };
+
+    let mut count = 0;
+    while completed_on(records, day) {
+        count += 1;
+        day -= Duration::days(1);
+    }
+    count
+}
+
+/// Best result for `date` and the current streak, or None if the challenge
+/// for that day hasn't been completed.
+pub fn summary(records: &[DailyRecord], date: NaiveDate) -> Option<DailySummary> {
+    let best = records
+        .iter()
+        .filter(|r| r.date == date && r.wpm.is_finite())
+        .max_by(|a, b| a.wpm.total_cmp(&b.wpm))?;
+
+    Some(DailySummary {
+        date,
+        best_wpm: best.wpm,
+        best_accuracy: best.accuracy,
+        streak: streak(records, date),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn record(date: &str, wpm: f64) -> DailyRecord {
+        DailyRecord {
+            date: day(date),
+            wpm,
+            accuracy: 100.0,
+        }
+    }
+
+    #[test]
+    fn seed_is_stable_per_day() {
+        assert_eq!(seed_for(day("2022-05-01")), 20220501);
+        assert_ne!(seed_for(day("2022-05-01")), seed_for(day("2022-05-02")));
+    }
+
+    #[test]
+    fn parse_skips_header_and_bad_rows() {
+        let records = parse("date,wpm,accuracy\n2022-05-01,80,97\nnonsense\n2022-05-02,81,99\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].wpm, 81.0);
+    }
+
+    #[test]
+    fn parse_skips_non_finite_results() {
+        let records = parse("date,wpm,accuracy\n2022-05-01,NaN,97\n2022-05-01,80,inf\n");
+        assert_eq!(records, vec![]);
+    }
+
+    #[test]
+    fn save_writes_the_header_once() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), &record("2022-05-01", 80.0)).unwrap();
+        save(dir.path(), &record("2022-05-02", 81.0)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(DAILY_FILE)).unwrap(),
+            "date,wpm,accuracy\n2022-05-01,80,100\n2022-05-02,81,100\n"
+        );
+        assert_eq!(load(dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn streak_counts_consecutive_days() {
+        let records = vec![
+            record("2022-04-28", 70.0),
+            record("2022-04-30", 70.0),
+            record("2022-05-01", 70.0),
+        ];
+        assert_eq!(streak(&records, day("2022-05-01")), 2);
+        // today not played yet: yesterday's streak still stands
+        assert_eq!(streak(&records, day("2022-05-02")), 2);
+        assert_eq!(streak(&records, day("2022-05-03")), 0);
+    }
+
+    #[test]
+    fn summary_picks_best_of_the_day() {
+        let records = vec![
+            record("2022-05-01", 70.0),
+            record("2022-05-01", 90.0),
+            record("2022-05-02", 100.0),
+        ];
+        let summary = summary(&records, day("2022-05-01")).unwrap();
+        assert_eq!(summary.best_wpm, 90.0);
+        assert_eq!(summary.streak, 1);
+        assert!(completed_on(&records, day("2022-05-02")));
+        assert_eq!(super::summary(&records, day("2022-05-03")), None);
+    }
+
+    #[test]
+    fn summary_tolerates_nan() {
+        let records = vec![record("2022-05-01", f64::NAN), record("2022-05-01", 90.0)];
+        assert_eq!(summary(&records, day("2022-05-01")).unwrap().best_wpm, 90.0);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::clock::FakeClock;
+use ratatui::crossterm::event::{self, Event};
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// how often a [`WakeOn`] checks whether it should wake up
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where a running test gets its keys (and resizes) from: the terminal, a
+/// replay, or another machine.
+pub trait EventSource {
+    /// The next event, waiting at most `timeout` (or for as long as it takes
+    /// without one); None if the timeout passed first.
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>>;
+}
+
+impl<S: EventSource + ?Sized> EventSource for &mut S {
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        (**self).next_event(timeout)
+    }
+}
+
+/// Events of the terminal thokr runs in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Crossterm;
+
+impl EventSource for Crossterm {
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        match timeout {
+            Some(timeout) if !event::poll(timeout)? => Ok(None),
+            _ => event::read().map(Some),
+        }
+    }
+}
+
+/// Events played back at set times on a [`FakeClock`], which waiting moves
+/// forward instead of sleeping.
+#[derive(Debug)]
+pub struct Replay {
+    clock: FakeClock,
+    start: Duration,
+    /// events and when they happen, relative to `start`
+    events: VecDeque<(Duration, Event)>,
+}
+
+impl Replay {
+    /// Plays `events`, each at its offset from the clock's current time.
+    /// Offsets must not decrease.
+    pub fn new(clock: FakeClock, events: impl IntoIterator<Item = (Duration, Event)>) -> Self {
+        Self {
+            start: clock.elapsed(),
+            clock,
+            events: events.into_iter().collect(),
+        }
+    }
+
+    /// Adds `event` after the last one, `after` it.
+    pub fn then(mut self, after: Duration, event: Event) -> Self {
+        let at = self.events.back().map_or(Duration::ZERO, |(at, _)| *at) + after;
+        self.events.push_back((at, event));
+        self
+    }
+}
+
+impl EventSource for Replay {
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let now = self.clock.elapsed();
+        let due = match self.events.front() {
+            Some((at, _)) => self.start + *at,
+            None if timeout.is_some() => Duration::MAX,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the replay has no events left",
+                ))
+            }
+        };
+
+        match timeout {
+            Some(timeout) if due > now + timeout => {
+                self.clock.advance(timeout);
+                Ok(None)
+            }
+            _ => {
+                self.clock.set(due.max(now));
+                Ok(self.events.pop_front().map(|(_, event)| event))
+            }
+        }
+    }
+}
+
+/// Events of `inner` that also stop waiting, with no event, as soon as
+/// `ready` holds, e.g. once news arrived over the network and the screen
+/// should be redrawn.
+pub struct WakeOn<S> {
+    inner: S,
+    ready: Box<dyn Fn() -> bool + Send>,
+}
+
+impl<S> WakeOn<S> {
+    pub fn new(inner: S, ready: impl Fn() -> bool + Send + 'static) -> Self {
+        Self {
+            inner,
+            ready: Box::new(ready),
+        }
+    }
+}
+
+impl<S: EventSource> EventSource for WakeOn<S> {
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if (self.ready)() {
+                return Ok(None);
+            }
+            let wait = deadline.map_or(POLL_INTERVAL, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL)
+            });
+            if let Some(event) = self.inner.next_event(Some(wait))? {
+                return Ok(Some(event));
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent::from(KeyCode::Char(c)))
+    }
+
+    #[test]
+    fn replay_waits_on_the_clock() {
+        let clock = FakeClock::new();
+        clock.set(Duration::from_secs(1));
+        let mut replay = Replay::new(
+            clock.clone(),
+            [
+                (Duration::ZERO, key('a')),
+                (Duration::from_millis(300), key('b')),
+            ],
+        );
+
+        assert_eq!(replay.next_event(None).unwrap(), Some(key('a')));
+        assert_eq!(clock.elapsed(), Duration::from_secs(1));
+
+        let tick = Some(Duration::from_millis(100));
+        assert_eq!(replay.next_event(tick).unwrap(), None);
+        assert_eq!(replay.next_event(tick).unwrap(), None);
+        assert_eq!(clock.elapsed(), Duration::from_millis(1200));
+
+        assert_eq!(replay.next_event(tick).unwrap(), Some(key('b')));
+        assert_eq!(clock.elapsed(), Duration::from_millis(1300));
+
+        assert_eq!(replay.next_event(tick).unwrap(), None);
+        assert_eq!(
+            replay.next_event(None).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::history::{Entry, Mode};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+
+/// What `thokr export` writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// one row per test
+    Csv,
+    /// an array of history entries
+    Json,
+    /// progress report
+    Markdown,
+    /// progress report as a standalone page
+    Html,
+}
+
+const CSV_HEADER: [&str; 21] = [
+    "date",
+    "mode",
+    "language",
+    "seed",
+    "pace",
+    "num_words",
+    "num_secs",
+    "elapsed_secs",
+    "wpm",
+    "accuracy",
+    "std_dev",
+    "prompt_hash",
+    "prompt_len",
+    "version",
+    "source",
+    "weighted",
+    "repeat_gap",
+    "markov_unit",
+    "markov_order",
+    "corpus",
+    "emulate_layout",
+];
+
+/// Entries recorded from `since` through `until`, going by the local date
+/// they were recorded on.
+pub fn filter(
+    entries: Vec<Entry>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .filter(|e| {
+            let date = e.date.date_naive();
+            since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until)
+        })
+        .collect()
+}
+
+pub fn render(entries: &[Entry], format: Format) -> String {
+    match format {
+        Format::Csv => csv(entries),
+        Format::Json => {
+            serde_json::to_string_pretty(entries).expect("history entries serialize to json") + "\n"
+        }
+        Format::Markdown => markdown(&report(entries)),
+        Format::Html => html(&report(entries)),
+    }
+}
+
+fn mode_name(mode: Option<Mode>) -> &'static str {
+    mode.map_or("unknown", |mode| mode.name())
+}
+
+/// e.g. "words", or "words (monkeytype)" for imported tests
+fn mode_label(entry: &Entry) -> String {
+    let mode = mode_name(entry.settings.mode);
+    match &entry.source {
+        Some(source) => format!("{} ({})", mode, source),
+        None => mode.to_string(),
+    }
+}
+
+fn csv_field(field: String) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn csv(entries: &[Entry]) -> String {
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    let mut out = CSV_HEADER.join(",") + "\n";
+    for e in entries {
+        let row = [
+            e.date.to_rfc3339(),
+            e.settings
+                .mode
+                .map(|m| m.name().to_string())
+                .unwrap_or_default(),
+            opt(e.settings.language.clone()),
+            opt(e.settings.seed.map(|s| s.to_string())),
+            opt(e.settings.pace.map(|p| p.to_string())),
+            e.num_words.to_string(),
+            opt(e.num_secs.map(|s| s.to_string())),
+            e.elapsed_secs.to_string(),
+            e.wpm.to_string(),
+            e.accuracy.to_string(),
+            e.std_dev.to_string(),
+            opt(e.prompt_hash.clone()),
+            opt(e.prompt_len.map(|l| l.to_string())),
+            opt(e.version.clone()),
+            opt(e.source.clone()),
+            opt(e.settings.weighted.map(|w| w.to_string())),
+            opt(e.settings.repeat_gap.map(|g| g.to_string())),
+            opt(e.settings.markov_unit.clone()),
+            opt(e.settings.markov_order.map(|o| o.to_string())),
+            opt(e.settings.corpus.clone()),
+            opt(e.settings.emulate_layout.clone()),
+        ];
+        out.push_str(&row.map(csv_field).join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// A titled table of the progress report.
+#[derive(Clone, Debug, PartialEq)]
+struct Table {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+/// Running totals of a set of tests.
+#[derive(Clone, Copy, Debug, Default)]
+struct Group {
+    tests: usize,
+    wpm: f64,
+    accuracy: f64,
+    best_wpm: f64,
+}
+
+impl Group {
+    fn add(&mut self, entry: &Entry) {
+        self.tests += 1;
+        self.wpm += entry.wpm;
+        self.accuracy += entry.accuracy;
+        self.best_wpm = self.best_wpm.max(entry.wpm);
+    }
+
+    fn row(&self, label: String) -> Vec<String> {
+        vec![
+            label,
+            self.tests.to_string(),
+            format!("{:.1}", self.wpm / self.tests as f64),
+            format!("{:.1}%", self.accuracy / self.tests as f64),
+            self.best_wpm.to_string(),
+        ]
+    }
+}
+
+/// e.g. "words, english, 15 words" or "daily, english1k, 30s"
+fn setup(entry: &Entry) -> String {
+    let mut parts = vec![mode_label(entry)];
+    parts.extend(entry.settings.language.clone());
+    parts.push(match entry.num_secs {
+        Some(secs) => format!("{}s", secs),
+        None => format!("{} words", entry.num_words),
+    });
+    parts.join(", ")
+}
+
+fn duration(secs: f64) -> String {
+    let mins = (secs / 60.0).round() as u64;
+    match mins / 60 {
+        0 => format!("{}m", mins),
+        hours => format!("{}h {}m", hours, mins % 60),
+    }
+}
+
+fn report(entries: &[Entry]) -> Vec<Table> {
+    let date = |e: &Entry| e.date.format("%Y-%m-%d").to_string();
+
+    let mut overall = Group::default();
+    let mut bests: BTreeMap<String, &Entry> = BTreeMap::new();
+    let mut weeks: BTreeMap<(i32, u32), Group> = BTreeMap::new();
+    let mut modes: BTreeMap<String, Group> = BTreeMap::new();
+
+    for entry in entries {
+        overall.add(entry);
+        let best = bests.entry(setup(entry)).or_insert(entry);
+        if entry.wpm > best.wpm {
+            *best = entry;
+        }
+        let week = entry.date.iso_week();
+        weeks
+            .entry((week.year(), week.week()))
+            .or_default()
+            .add(entry);
+        modes.entry(mode_label(entry)).or_default().add(entry);
+    }
+
+    let mut summary = vec![vec![String::from("tests"), entries.len().to_string()]];
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        summary.extend([
+            vec![String::from("from"), date(first)],
+            vec![String::from("to"), date(last)],
+            vec![
+                String::from("time typing"),
+                duration(entries.iter().map(|e| e.elapsed_secs).sum()),
+            ],
+            vec![
+                String::from("avg wpm"),
+                format!("{:.1}", overall.wpm / overall.tests as f64),
+            ],
+            vec![
+                String::from("avg accuracy"),
+                format!("{:.1}%", overall.accuracy / overall.tests as f64),
+            ],
+        ]);
+    }
+
+    let mut bests = bests.into_iter().collect::<Vec<_>>();
+    bests.sort_by(|a, b| b.1.wpm.total_cmp(&a.1.wpm));
+
+    vec![
+        Table {
+            title: "Summary",
+            headers: &["", "all tests"],
+            rows: summary,
+        },
+        Table {
+            title: "Personal bests",
+            headers: &["setup", "wpm", "accuracy", "date"],
+            rows: bests
+                .into_iter()
+                .map(|(setup, e)| {
+                    vec![
+                        setup,
+                        e.wpm.to_string(),
+                        format!("{}%", e.accuracy),
+                        date(e),
+                    ]
+                })
+                .collect(),
+        },
+        Table {
+            title: "Weekly averages",
+            headers: &["week", "tests", "avg wpm", "avg accuracy", "best wpm"],
+            rows: weeks
+                .into_iter()
+                .map(|((year, week), group)| group.row(format!("{}-W{:02}", year, week)))
+                .collect(),
+        },
+        Table {
+            title: "By mode",
+            headers: &["mode", "tests", "avg wpm", "avg accuracy", "best wpm"],
+            rows: modes
+                .into_iter()
+                .map(|(mode, group)| group.row(mode))
+                .collect(),
+        },
+    ]
+}
+
+fn markdown(tables: &[Table]) -> String {
+    let mut out = String::from("# thokr progress report\n");
+    for table in tables {
+        out.push_str(&format!("\n## {}\n\n", table.title));
+        if table.rows.is_empty() {
+            out.push_str("_no tests_\n");
+            continue;
+        }
+        let cells = |cells: &[String]| format!("| {} |\n", cells.join(" | "));
+        out.push_str(&cells(
+            &table
+                .headers
+                .iter()
+                .map(|h| h.to_string())
+                .collect::<Vec<_>>(),
+        ));
+        // first column is a label, the rest are numbers
+        let align = (0..table.headers.len())
+            .map(|i| if i == 0 { ":--" } else { "--:" }.to_string())
+            .collect::<Vec<_>>();
+        out.push_str(&cells(&align));
+        for row in &table.rows {
+            out.push_str(&cells(row));
+        }
+    }
+    out
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html(tables: &[Table]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>thokr progress report</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; }\n\
+         td + td { text-align: right; }\n\
+         </style>\n</head>\n<body>\n<h1>thokr progress report</h1>\n",
+    );
+    for table in tables {
+        out.push_str(&format!("<h2>{}</h2>\n", table.title));
+        if table.rows.is_empty() {
+            out.push_str("<p><em>no tests</em></p>\n");
+            continue;
+        }
+        out.push_str("<table>\n<tr>");
+        for header in table.headers {
+            out.push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        out.push_str("</tr>\n");
+        for row in &table.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Settings;
+    use chrono::DateTime;
+
+    fn entry(date: &str, mode: Mode, wpm: f64) -> Entry {
+        Entry {
+            date: DateTime::parse_from_rfc3339(date).unwrap(),
+            num_words: 15,
+            num_secs: None,
+            elapsed_secs: 12.0,
+            wpm,
+            accuracy: 95.0,
+            std_dev: 3.0,
+            settings: Settings {
+                mode: Some(mode),
+                language: Some("english".to_string()),
+                ..Settings::default()
+            },
+            prompt_hash: None,
+            prompt_len: None,
+            version: None,
+            source: None,
+        }
+    }
+
+    fn history() -> Vec<Entry> {
+        vec![
+            entry("2022-05-02T09:00:00+02:00", Mode::Words, 60.0),
+            entry("2022-05-03T09:00:00+02:00", Mode::Words, 80.0),
+            entry("2022-05-10T09:00:00+02:00", Mode::Daily, 70.0),
+        ]
+    }
+
+    #[test]
+    fn filter_is_inclusive() {
+        let day = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(filter(history(), Some(day("2022-05-03")), None).len(), 2);
+        assert_eq!(filter(history(), None, Some(day("2022-05-03"))).len(), 2);
+        assert_eq!(
+            filter(history(), Some(day("2022-05-03")), Some(day("2022-05-03"))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn csv_has_a_row_per_test() {
+        let mut entries = history();
+        entries[0].settings.language = Some("with, comma".to_string());
+        entries[1].settings.weighted = Some(true);
+        entries[1].settings.repeat_gap = Some(3);
+        entries[2].settings = Settings {
+            mode: Some(Mode::Markov),
+            markov_unit: Some("word".to_string()),
+            markov_order: Some(2),
+            corpus: Some("book.txt".to_string()),
+            emulate_layout: Some("dvorak".to_string()),
+            ..Settings::default()
+        };
+        let csv = render(&entries, Format::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("date,mode,language,"));
+        assert!(lines[0].ends_with(
+            ",source,weighted,repeat_gap,markov_unit,markov_order,corpus,emulate_layout"
+        ));
+        assert!(lines[1].starts_with("2022-05-02T09:00:00+02:00,words,\"with, comma\",,,15,"));
+        assert!(lines[1].ends_with(",,,,,,,"));
+        assert!(lines[2].ends_with(",true,3,,,,"));
+        assert!(lines[3].ends_with(",word,2,book.txt,dvorak"));
+    }
+
+    #[test]
+    fn report_groups_by_week_and_mode() {
+        let tables = report(&history());
+
+        let bests = &tables[1].rows;
+        assert_eq!(bests[0][..2], ["words, english, 15 words", "80"]);
+        assert_eq!(bests[1][..2], ["daily, english, 15 words", "70"]);
+
+        let weeks = &tables[2].rows;
+        assert_eq!(weeks[0], ["2022-W18", "2", "70.0", "95.0%", "80"]);
+        assert_eq!(weeks[1][..2], ["2022-W19", "1"]);
+
+        let modes = &tables[3].rows;
+        assert_eq!(modes[0][..2], ["daily", "1"]);
+        assert_eq!(modes[1][..2], ["words", "2"]);
+    }
+
+    #[test]
+    fn markdown_and_html_reports() {
+        let md = render(&history(), Format::Markdown);
+        assert!(md.contains("## Weekly averages\n\n| week | tests |"));
+        assert!(md.contains("| 2022-W18 | 2 | 70.0 | 95.0% | 80 |"));
+
+        let html = render(&[], Format::Html);
+        assert!(html.contains("<h2>Personal bests</h2>\n<p><em>no tests</em></p>"));
+    }
+}
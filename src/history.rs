@@ -0,0 +1,681 @@
+use crate::thok::Thok;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Version of the history format, written on the first line of the file.
+/// Bump it whenever an existing field changes meaning.
+pub const VERSION: u32 = 1;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// how much of either end of the history `append` reads, see `open_healthy`
+const TAIL_BYTES: u64 = 8192;
+
+/// held while the history is written
+const LOCK_FILE: &str = "history.lock";
+
+/// lines of the history that couldn't be read end up here
+const CORRUPT_FILE: &str = "history.corrupt";
+
+/// CSV log written by thokr before the history was versioned
+const LEGACY_LOG_FILE: &str = "log.csv";
+
+/// The legacy log is kept under this name once migrated.
+const MIGRATED_LOG_FILE: &str = "log.csv.migrated";
+
+/// First line of the history file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    version: u32,
+}
+
+/// Kind of prompt a test was taken on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Words,
+    Sentences,
+    Prompt,
+    Daily,
+    Markov,
+    Review,
+    Lesson,
+}
+
+impl Mode {
+    /// As written in the history.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Words => "words",
+            Mode::Sentences => "sentences",
+            Mode::Prompt => "prompt",
+            Mode::Daily => "daily",
+            Mode::Markov => "markov",
+            Mode::Review => "review",
+            Mode::Lesson => "lesson",
+        }
+    }
+}
+
+/// How the prompt of a test was generated, so results can be compared
+/// like-for-like. Entries migrated from `log.csv` have none of these.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub mode: Option<Mode>,
+    /// word list the prompt was drawn from, as passed to `--supported-language`
+    pub language: Option<String>,
+    pub seed: Option<u64>,
+    /// wpm of the pace caret
+    pub pace: Option<f64>,
+    /// whether common words were favored, for word tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weighted: Option<bool>,
+    /// minimum words between repeats, for word tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_gap: Option<usize>,
+    /// "char" or "word", for Markov tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markov_unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markov_order: Option<usize>,
+    /// file the Markov chain was trained on, None for the built-in corpus
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corpus: Option<String>,
+    /// layout typed keys were remapped to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emulate_layout: Option<String>,
+}
+
+/// One finished test.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// when the test finished, in ISO-8601 with the local offset
+    pub date: DateTime<FixedOffset>,
+    pub num_words: usize,
+    pub num_secs: Option<f64>,
+    pub elapsed_secs: f64,
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub std_dev: f64,
+    #[serde(flatten)]
+    pub settings: Settings,
+    /// see `prompt_hash`
+    pub prompt_hash: Option<String>,
+    /// prompt length in chars
+    pub prompt_len: Option<usize>,
+    /// thokr version that recorded the test
+    pub version: Option<String>,
+    /// typing trainer the test was imported from, None for thokr's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Entry {
+    /// The entry recording `thok`, a finished test.
+    pub fn of(thok: &Thok, settings: &Settings) -> Self {
+        let elapsed_secs = thok.elapsed_secs().unwrap();
+
+        Self {
+            date: thok.finished_at.unwrap_or_else(Local::now).fixed_offset(),
+            num_words: thok.number_of_words,
+            num_secs: thok.number_of_secs,
+            elapsed_secs: (elapsed_secs * 100.0).round() / 100.0,
+            wpm: thok.wpm,           // already rounded
+            accuracy: thok.accuracy, // already rounded
+            std_dev: (thok.std_dev * 100.0).round() / 100.0,
+            settings: settings.clone(),
+            prompt_hash: Some(prompt_hash(&thok.prompt)),
+            prompt_len: Some(thok.prompt_chars.len()),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            source: None,
+        }
+    }
+}
+
+/// A stable fingerprint of `prompt` (64-bit FNV-1a, as hex), identical across
+/// platforms and thokr versions so reruns of the same prompt can be matched.
+pub fn prompt_hash(prompt: &str) -> String {
+    let hash = prompt
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Takes the advisory lock that serializes writers of the history and daily
+/// log in `dir`, e.g. thokr running in two tmux panes. Released when the
+/// file is dropped.
+pub(crate) fn lock(dir: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Appends `entry` to the history in `dir`, migrating a legacy `log.csv`
+/// first. Returns how many malformed lines were moved out of the way to
+/// `history.corrupt` while doing so.
+pub fn append(dir: &Path, entry: &Entry) -> io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let _lock = lock(dir)?;
+    migrate(dir)?;
+
+    let path = dir.join(HISTORY_FILE);
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    // the common case: a single write to the end of a healthy file
+    if let Some(mut file) = open_healthy(&path)? {
+        file.write_all(line.as_bytes())?;
+        return Ok(0);
+    }
+
+    // a new file, or one with a lost header or a partial last line (e.g.
+    // from a crash mid-write): set the bad lines aside and rewrite the rest
+    let contents = read_existing(&path)?;
+    let parsed = parse(&contents);
+    check_version(&path, &parsed)?;
+    set_aside(dir, &parsed.malformed)?;
+    let mut rewritten = serde_json::to_string(&Header { version: VERSION })?;
+    rewritten.push('\n');
+    for valid in &parsed.entries {
+        rewritten.push_str(valid);
+        rewritten.push('\n');
+    }
+    rewritten.push_str(&line);
+    replace(&path, &rewritten)?;
+
+    Ok(parsed.malformed.len())
+}
+
+/// Adds `entries` (e.g. imported from another typing trainer) to the history
+/// in `dir`, keeping it sorted by date. Tests already in the history, going by
+/// when they were taken and their wpm, are skipped so importing the same file
+/// twice is harmless. Returns how many entries were added.
+pub fn merge(dir: &Path, entries: Vec<Entry>) -> io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let _lock = lock(dir)?;
+    migrate(dir)?;
+
+    let path = dir.join(HISTORY_FILE);
+    let contents = read_existing(&path)?;
+    let parsed = parse(&contents);
+    check_version(&path, &parsed)?;
+    set_aside(dir, &parsed.malformed)?;
+
+    // existing lines are kept as they are, fields this version doesn't know
+    // about included
+    let key = |e: &Entry| (e.date.timestamp_millis(), e.wpm.to_bits());
+    let mut all = vec![];
+    for line in &parsed.entries {
+        let entry: Entry = serde_json::from_str(line)?;
+        all.push((entry, line.to_string()));
+    }
+    let mut seen = all.iter().map(|(e, _)| key(e)).collect::<HashSet<_>>();
+    let before = all.len();
+    for entry in entries {
+        if seen.insert(key(&entry)) {
+            let line = serde_json::to_string(&entry)?;
+            all.push((entry, line));
+        }
+    }
+    let added = all.len() - before;
+    all.sort_by_key(|(e, _)| e.date);
+
+    let mut rewritten = serde_json::to_string(&Header { version: VERSION })?;
+    rewritten.push('\n');
+    for (_, line) in &all {
+        rewritten.push_str(line);
+        rewritten.push('\n');
+    }
+    replace(&path, &rewritten)?;
+
+    Ok(added)
+}
+
+/// The history at `path`, opened for appending, if it can simply be appended
+/// to: it starts with the header of this version and ends with a whole line
+/// that reads. Only its ends are read, so lines in between that don't read
+/// are left for `load` to skip and the next rewrite to set aside.
+fn open_healthy(path: &Path) -> io::Result<Option<File>> {
+    let mut file = match OpenOptions::new().read(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut head = vec![];
+    BufReader::new(&file)
+        .take(TAIL_BYTES)
+        .read_until(b'\n', &mut head)?;
+    let header = std::str::from_utf8(&head)
+        .ok()
+        .and_then(|line| serde_json::from_str::<Header>(line).ok());
+    if header != Some(Header { version: VERSION }) {
+        return Ok(None);
+    }
+
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(TAIL_BYTES);
+    let mut tail = vec![];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_to_end(&mut tail)?;
+    let Some(tail) = tail.strip_suffix(b"\n") else {
+        return Ok(None);
+    };
+    let last = match tail.iter().rposition(|&b| b == b'\n') {
+        Some(newline) => &tail[newline + 1..],
+        // the header is the only line
+        None if start == 0 => return Ok(Some(file)),
+        // too long to be an entry
+        None => return Ok(None),
+    };
+    let readable = std::str::from_utf8(last)
+        .ok()
+        .is_some_and(|line| serde_json::from_str::<Entry>(line).is_ok());
+    Ok(readable.then_some(file))
+}
+
+/// The contents of the history file at `path`, empty if there is none yet.
+fn read_existing(path: &Path) -> io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Refuses to touch a history written by a newer, incompatible thokr.
+fn check_version(path: &Path, parsed: &Parsed) -> io::Result<()> {
+    match parsed.version {
+        Some(version) if version > VERSION => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} uses history format {} but this thokr only knows up to {}",
+                path.display(),
+                version,
+                VERSION
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Appends unreadable history lines to `history.corrupt` in `dir`.
+fn set_aside(dir: &Path, malformed: &[&str]) -> io::Result<()> {
+    if malformed.is_empty() {
+        return Ok(());
+    }
+    let mut corrupt = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(dir.join(CORRUPT_FILE))?;
+    for bad in malformed {
+        writeln!(corrupt, "{}", bad)?;
+    }
+    Ok(())
+}
+
+/// Every readable entry of the history in `dir`, oldest first. A legacy
+/// `log.csv` that hasn't been migrated yet is read instead.
+pub fn load(dir: &Path) -> io::Result<Vec<Entry>> {
+    let not_found = |e: &io::Error| e.kind() == io::ErrorKind::NotFound;
+    match std::fs::read_to_string(dir.join(HISTORY_FILE)) {
+        Ok(contents) => Ok(parse(&contents)
+            .entries
+            .into_iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()),
+        Err(e) if not_found(&e) => match std::fs::read_to_string(dir.join(LEGACY_LOG_FILE)) {
+            Ok(contents) => Ok(parse_legacy(&contents)),
+            Err(e) if not_found(&e) => Ok(vec![]),
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Atomically replaces the contents of `path`: readers see either the old
+/// or the new file, never a half-written one.
+pub(crate) fn replace(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(contents.as_bytes())?;
+    tmp.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+/// The lines of a history file, sorted by whether they can be read.
+#[derive(Debug, Default, PartialEq)]
+struct Parsed<'a> {
+    /// None when the header line is missing
+    version: Option<u32>,
+    entries: Vec<&'a str>,
+    malformed: Vec<&'a str>,
+}
+
+fn parse(contents: &str) -> Parsed<'_> {
+    let mut parsed = Parsed::default();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if i == 0 {
+            if let Ok(header) = serde_json::from_str::<Header>(line) {
+                parsed.version = Some(header.version);
+                continue;
+            }
+        }
+        if serde_json::from_str::<Entry>(line).is_ok() {
+            parsed.entries.push(line);
+        } else {
+            parsed.malformed.push(line);
+        }
+    }
+    parsed
+}
+
+/// Converts a legacy `log.csv` into a fresh history file, keeping the old
+/// log around as `log.csv.migrated`. Does nothing once a history exists.
+fn migrate(dir: &Path) -> io::Result<()> {
+    let legacy_path = dir.join(LEGACY_LOG_FILE);
+    let path = dir.join(HISTORY_FILE);
+    if path.exists() || !legacy_path.exists() {
+        return Ok(());
+    }
+
+    let mut contents = serde_json::to_string(&Header { version: VERSION })?;
+    contents.push('\n');
+    for entry in parse_legacy(&std::fs::read_to_string(&legacy_path)?) {
+        contents.push_str(&serde_json::to_string(&entry)?);
+        contents.push('\n');
+    }
+
+    // write the whole file before it appears under its real name, so an
+    // interrupted migration is simply retried next time
+    replace(&path, &contents)?;
+    std::fs::rename(legacy_path, dir.join(MIGRATED_LOG_FILE))
+}
+
+/// Rows of a legacy `log.csv`; rows that can't be read are skipped.
+fn parse_legacy(contents: &str) -> Vec<Entry> {
+    contents
+        .lines()
+        .skip(1) // header
+        .filter_map(|line| {
+            let mut fields = line.split(',');
+            // written with `Local::now().format("%c")`, e.g. "Sun May  1 12:00:00 2022"
+            let date = NaiveDateTime::parse_from_str(fields.next()?, "%c").ok()?;
+            Some(Entry {
+                date: Local.from_local_datetime(&date).earliest()?.fixed_offset(),
+                num_words: fields.next()?.parse().ok()?,
+                num_secs: match fields.next()? {
+                    "" => None,
+                    ns => Some(ns.parse().ok()?),
+                },
+                elapsed_secs: fields.next()?.parse().ok()?,
+                wpm: fields.next()?.parse().ok()?,
+                accuracy: fields.next()?.parse().ok()?,
+                std_dev: fields.next()?.parse().ok()?,
+                settings: Settings::default(),
+                prompt_hash: None,
+                prompt_len: None,
+                version: None,
+                source: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(wpm: f64) -> Entry {
+        Entry {
+            date: DateTime::parse_from_rfc3339("2022-05-01T12:00:00+02:00").unwrap(),
+            num_words: 15,
+            num_secs: None,
+            elapsed_secs: 9.5,
+            wpm,
+            accuracy: 97.5,
+            std_dev: 3.1,
+            settings: Settings {
+                mode: Some(Mode::Words),
+                language: Some("english".to_string()),
+                seed: Some(7),
+                ..Settings::default()
+            },
+            prompt_hash: Some(prompt_hash("the quick brown fox")),
+            prompt_len: Some(19),
+            version: Some("0.5.0".to_string()),
+            source: None,
+        }
+    }
+
+    fn lines(dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join(HISTORY_FILE))
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn entries_follow_a_version_header() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        append(dir, &entry(80.0)).unwrap();
+        append(dir, &entry(90.0)).unwrap();
+
+        let lines = lines(dir);
+        assert_eq!(lines[0], r#"{"version":1}"#);
+        assert!(lines[1].contains(r#""date":"2022-05-01T12:00:00+02:00""#));
+        assert_eq!(
+            serde_json::from_str::<Entry>(&lines[2]).unwrap(),
+            entry(90.0)
+        );
+    }
+
+    #[test]
+    fn settings_are_recorded_alongside_results() {
+        let json = serde_json::to_string(&entry(80.0)).unwrap();
+        assert!(json.contains(r#""mode":"words","language":"english","seed":7"#));
+
+        // entries written before settings were recorded still load
+        let old: Entry = serde_json::from_str(
+            r#"{"date":"2022-05-01T12:00:00+02:00","num_words":15,"num_secs":null,
+                "elapsed_secs":9.5,"wpm":80.0,"accuracy":97.5,"std_dev":3.1}"#,
+        )
+        .unwrap();
+        assert_eq!(old.settings, Settings::default());
+        assert_eq!(old.version, None);
+    }
+
+    #[test]
+    fn prompt_hash_is_stable() {
+        assert_eq!(prompt_hash(""), "cbf29ce484222325");
+        assert_eq!(prompt_hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(prompt_hash("the cat"), prompt_hash("the bat"));
+    }
+
+    #[test]
+    fn legacy_log_is_migrated() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        std::fs::write(
+            dir.join(LEGACY_LOG_FILE),
+            "date,num_words,num_secs,elapsed_secs,wpm,accuracy,std_dev\n\
+             Sun May  1 12:00:00 2022,15,,9.50,80,97.5,3.10\n\
+             Mon May  2 08:30:00 2022,15,30.00,30.00,85,96,4.00\n\
+             garbage\n",
+        )
+        .unwrap();
+
+        append(dir, &entry(90.0)).unwrap();
+
+        let lines = lines(dir);
+        assert_eq!(lines.len(), 4);
+        let first: Entry = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(first.date.naive_local().to_string(), "2022-05-01 12:00:00");
+        assert_eq!(first.num_secs, None);
+        assert_eq!(first.wpm, 80.0);
+        let second: Entry = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(second.num_secs, Some(30.0));
+        assert!(!dir.join(LEGACY_LOG_FILE).exists());
+        assert!(dir.join(MIGRATED_LOG_FILE).exists());
+    }
+
+    #[test]
+    fn malformed_lines_are_set_aside() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let valid = serde_json::to_string(&entry(80.0)).unwrap();
+        std::fs::write(
+            dir.join(HISTORY_FILE),
+            format!(
+                "{{\"version\":1}}\n{}\nnot json\n{{\"date\":\"2022-05",
+                valid
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(append(dir, &entry(90.0)).unwrap(), 2);
+
+        let lines = lines(dir);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], valid);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(CORRUPT_FILE)).unwrap(),
+            "not json\n{\"date\":\"2022-05\n"
+        );
+        // the file is healthy again
+        assert_eq!(append(dir, &entry(95.0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn healthy_history_is_only_appended_to() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        append(dir, &entry(80.0)).unwrap();
+        // a garbled line in the middle is only dealt with on a rewrite
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(HISTORY_FILE))
+            .unwrap();
+        writeln!(file, "not json").unwrap();
+        writeln!(file, "{}", serde_json::to_string(&entry(85.0)).unwrap()).unwrap();
+
+        assert_eq!(append(dir, &entry(90.0)).unwrap(), 0);
+        assert_eq!(lines(dir)[2], "not json");
+        assert_eq!(load(dir).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn lost_header_is_restored() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let valid = serde_json::to_string(&entry(80.0)).unwrap();
+        std::fs::write(dir.join(HISTORY_FILE), &valid).unwrap();
+
+        assert_eq!(append(dir, &entry(90.0)).unwrap(), 0);
+        let lines = lines(dir);
+        assert_eq!(lines[0], r#"{"version":1}"#);
+        assert_eq!(lines[1], valid);
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn concurrent_writers_dont_interleave() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let writers = (0..4)
+            .map(|i| {
+                let dir = dir.to_path_buf();
+                std::thread::spawn(move || {
+                    for j in 0..25 {
+                        append(&dir, &entry((i * 100 + j) as f64)).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let contents = std::fs::read_to_string(dir.join(HISTORY_FILE)).unwrap();
+        let parsed = parse(&contents);
+        assert_eq!(parsed.version, Some(VERSION));
+        assert_eq!(parsed.entries.len(), 100);
+        assert!(parsed.malformed.is_empty());
+    }
+
+    #[test]
+    fn load_skips_unreadable_lines() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        assert_eq!(load(dir).unwrap(), vec![]);
+
+        append(dir, &entry(80.0)).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(HISTORY_FILE))
+            .unwrap();
+        writeln!(file, "{{\"date\":").unwrap();
+        assert_eq!(load(dir).unwrap(), vec![entry(80.0)]);
+    }
+
+    #[test]
+    fn merge_sorts_and_skips_duplicates() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        append(dir, &entry(80.0)).unwrap();
+
+        let mut older = entry(50.0);
+        older.date = DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap();
+        older.source = Some("monkeytype".to_string());
+        assert_eq!(merge(dir, vec![older.clone(), entry(80.0)]).unwrap(), 1);
+        assert_eq!(merge(dir, vec![older.clone()]).unwrap(), 0);
+
+        assert_eq!(load(dir).unwrap(), vec![older, entry(80.0)]);
+    }
+
+    #[test]
+    fn merge_keeps_existing_lines_as_they_are() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        append(dir, &entry(80.0)).unwrap();
+        let mut newer = serde_json::to_value(entry(90.0)).unwrap();
+        newer["date"] = serde_json::json!("2030-01-01T00:00:00Z");
+        newer["from_the_future"] = serde_json::json!(true);
+        let newer = serde_json::to_string(&newer).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(HISTORY_FILE))
+            .unwrap();
+        writeln!(file, "{}", newer).unwrap();
+
+        let mut older = entry(50.0);
+        older.date = DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap();
+        assert_eq!(merge(dir, vec![older]).unwrap(), 1);
+        assert_eq!(lines(dir)[3], newer);
+    }
+
+    #[test]
+    fn newer_history_is_left_alone() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        std::fs::write(dir.join(HISTORY_FILE), "{\"version\":99}\n").unwrap();
+        assert!(append(dir, &entry(80.0)).is_err());
+        assert_eq!(lines(dir).len(), 1);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::history::{Entry, Mode, Settings};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use std::io;
+
+/// Typing trainers whose exported results can be imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// the CSV from monkeytype's account page
+    Monkeytype,
+    /// a race history CSV with at least date, wpm, accuracy and duration
+    /// columns, as exported from typeracer and similar sites
+    Typeracer,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Monkeytype => "monkeytype",
+            Source::Typeracer => "typeracer",
+        }
+    }
+}
+
+/// What was read from an export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Imported {
+    pub entries: Vec<Entry>,
+    /// rows that couldn't be read
+    pub skipped: usize,
+}
+
+/// Records of a CSV file. Quoted fields may contain commas, newlines and
+/// doubled quotes; blank lines are dropped.
+fn read_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    records
+}
+
+/// Index of the first column named like one of `names`, ignoring case.
+fn column(headers: &[String], names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    })
+}
+
+fn missing_column(source: Source, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("not a {} export: no {} column", source.name(), what),
+    )
+}
+
+/// Accuracy as a percentage. Monkeytype always writes a percentage like
+/// "98.5"; typeracer writes a fraction like "0.985", and sites that write a
+/// percentage instead mark it, as in "98.5%".
+fn parse_accuracy(source: Source, s: &str) -> Option<f64> {
+    let s = s.trim();
+    match source {
+        Source::Monkeytype => s.parse().ok(),
+        Source::Typeracer => match s.strip_suffix('%') {
+            Some(percent) => percent.trim().parse().ok(),
+            None => s.parse::<f64>().ok().map(|fraction| fraction * 100.0),
+        },
+    }
+}
+
+/// A date as written by the usual exports, taken as UTC unless it says
+/// otherwise.
+fn parse_date(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Some(date);
+    }
+    [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%m/%d/%Y %H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .map(|date| date.and_utc().fixed_offset())
+}
+
+/// Exported results in `contents` as history entries. Exports don't record
+/// the spread of wpm over a test, so `std_dev` is 0; word counts that aren't
+/// part of the export are estimated from wpm and duration.
+pub fn parse(source: Source, contents: &str) -> io::Result<Imported> {
+    let mut records = read_csv(contents).into_iter();
+    let headers = records
+        .next()
+        .ok_or_else(|| missing_column(source, "header"))?;
+    let rows = records.collect::<Vec<_>>();
+
+    let entries = match source {
+        Source::Monkeytype => monkeytype(&headers, &rows)?,
+        Source::Typeracer => typeracer(&headers, &rows)?,
+    };
+    Ok(Imported {
+        skipped: rows.len() - entries.len(),
+        entries,
+    })
+}
+
+fn entry(
+    source: Source,
+    date: DateTime<FixedOffset>,
+    wpm: f64,
+    accuracy: f64,
+    elapsed_secs: f64,
+) -> Entry {
+    Entry {
+        date,
+        // wpm counts 5-char words, so this is what was typed in that time
+        num_words: (wpm * elapsed_secs / 60.0).round() as usize,
+        num_secs: None,
+        elapsed_secs,
+        wpm: wpm.round(),
+        accuracy: (accuracy * 100.0).round() / 100.0,
+        std_dev: 0.0,
+        settings: Settings::default(),
+        prompt_hash: None,
+        prompt_len: None,
+        version: None,
+        source: Some(source.name().to_string()),
+    }
+}
+
+fn monkeytype(headers: &[String], rows: &[Vec<String>]) -> io::Result<Vec<Entry>> {
+    let source = Source::Monkeytype;
+    let col =
+        |names: &[&str], what| column(headers, names).ok_or_else(|| missing_column(source, what));
+    let timestamp = col(&["timestamp"], "timestamp")?;
+    let wpm = col(&["wpm"], "wpm")?;
+    let acc = col(&["acc"], "acc")?;
+    let duration = col(&["testDuration"], "testDuration")?;
+    let mode = column(headers, &["mode"]);
+    let mode2 = column(headers, &["mode2"]);
+    let language = column(headers, &["language"]);
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let field = |i: usize| row.get(i).map(|f| f.trim());
+            let millis: i64 = field(timestamp)?.parse().ok()?;
+            let mut entry = entry(
+                source,
+                DateTime::<Utc>::from_timestamp_millis(millis)?.fixed_offset(),
+                field(wpm)?.parse().ok()?,
+                parse_accuracy(source, field(acc)?)?,
+                field(duration)?.parse().ok()?,
+            );
+
+            // mode2 is the test length: seconds for "time", words for "words"
+            let length = mode2.and_then(field).and_then(|l| l.parse::<usize>().ok());
+            entry.settings.mode = Some(match mode.and_then(field) {
+                Some("time") => {
+                    entry.num_secs = length.map(|secs| secs as f64);
+                    Mode::Words
+                }
+                Some("words") => {
+                    entry.num_words = length.unwrap_or(entry.num_words);
+                    Mode::Words
+                }
+                _ => Mode::Prompt,
+            });
+            entry.settings.language = language.and_then(field).map(language_name);
+            Some(entry)
+        })
+        .collect())
+}
+
+/// thokr's name for a monkeytype language, e.g. "english_1k" => "english1k";
+/// languages thokr doesn't have keep monkeytype's name.
+fn language_name(language: &str) -> String {
+    match language {
+        "english_1k" => "english1k",
+        "english_10k" => "english10k",
+        other => other,
+    }
+    .to_string()
+}
+
+fn typeracer(headers: &[String], rows: &[Vec<String>]) -> io::Result<Vec<Entry>> {
+    let source = Source::Typeracer;
+    let col =
+        |names: &[&str], what| column(headers, names).ok_or_else(|| missing_column(source, what));
+    let date = col(
+        &["Date/Time (UTC)", "Date/Time", "Date", "Timestamp"],
+        "date",
+    )?;
+    let wpm = col(&["WPM", "Speed"], "wpm")?;
+    let acc = col(&["Accuracy", "Acc"], "accuracy")?;
+    let duration = col(&["Time", "Duration", "Seconds"], "duration")?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let field = |i: usize| row.get(i).map(|f| f.trim());
+            let mut entry = entry(
+                source,
+                parse_date(field(date)?)?,
+                field(wpm)?.parse().ok()?,
+                parse_accuracy(source, field(acc)?)?,
+                field(duration)?
+                    .parse()
+                    .ok()
+                    .filter(|&secs: &f64| secs > 0.0)?,
+            );
+            // races are quotes, much like a custom prompt
+            entry.settings.mode = Some(Mode::Prompt);
+            entry.settings.language = Some(String::from("english"));
+            Some(entry)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        let records = read_csv("a,\"b, \"\"c\"\"\",d\r\n\n\"multi\nline\",,\n");
+        assert_eq!(
+            records,
+            vec![vec!["a", "b, \"c\"", "d"], vec!["multi\nline", "", ""],]
+        );
+    }
+
+    #[test]
+    fn monkeytype_export() {
+        let csv = "_id,isPb,wpm,acc,rawWpm,consistency,charStats,mode,mode2,quoteLength,testDuration,timestamp,language\n\
+                   a1,true,92.5,97.12,95,80,\"120;2;0;0\",time,30,-1,30,1651406400000,english\n\
+                   a2,false,70,95,72,75,\"50;1;0;0\",words,25,-1,21.4,1651492800000,english_1k\n\
+                   a3,false,bad,95,72,75,,words,25,-1,21.4,1651492800000,english\n\
+                   a4,false,12,0.5,13,20,,time,15,-1,15,1651579200000,english\n";
+        let imported = parse(Source::Monkeytype, csv).unwrap();
+        assert_eq!(imported.skipped, 1);
+
+        let time = &imported.entries[0];
+        assert_eq!(time.date.to_rfc3339(), "2022-05-01T12:00:00+00:00");
+        assert_eq!((time.wpm, time.accuracy), (93.0, 97.12));
+        assert_eq!((time.num_secs, time.elapsed_secs), (Some(30.0), 30.0));
+        assert_eq!(time.settings.mode, Some(Mode::Words));
+        assert_eq!(time.source.as_deref(), Some("monkeytype"));
+
+        let words = &imported.entries[1];
+        assert_eq!((words.num_words, words.num_secs), (25, None));
+        assert_eq!(words.settings.language.as_deref(), Some("english1k"));
+
+        // monkeytype's accuracy is a percentage even when it's tiny
+        assert_eq!(imported.entries[2].accuracy, 0.5);
+    }
+
+    #[test]
+    fn monkeytype_languages_are_renamed() {
+        assert_eq!(language_name("english_1k"), "english1k");
+        assert_eq!(language_name("english_10k"), "english10k");
+        assert_eq!(language_name("english"), "english");
+        assert_eq!(language_name("german"), "german");
+    }
+
+    #[test]
+    fn typeracer_export() {
+        let csv = "Race #,WPM,Accuracy,Rank,# Racers,Text ID,Time,Date/Time (UTC)\n\
+                   812,88.2,0.98,1,5,3550042,40.8,2022-05-01 12:00:00\n\
+                   811,84,96%,2,5,3550041,42.9,2022-04-30 08:15:30\n\
+                   810,80,1,2,5,3550040,45,2022-04-29 08:15:30\n\
+                   809,80,1,2,5,3550039,,2022-04-28 08:15:30\n";
+        let imported = parse(Source::Typeracer, csv).unwrap();
+        // without a duration there's nothing to base the word count on
+        assert_eq!(imported.skipped, 1);
+        assert_eq!(imported.entries[0].accuracy, 98.0);
+        assert_eq!(imported.entries[0].num_words, 60);
+        assert_eq!(imported.entries[1].accuracy, 96.0);
+        assert_eq!(imported.entries[2].accuracy, 100.0);
+        assert_eq!(
+            imported.entries[1].date.to_rfc3339(),
+            "2022-04-30T08:15:30+00:00"
+        );
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let err = parse(Source::Monkeytype, "date,wpm\n2022-05-01,80\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not a monkeytype export: no timestamp column"
+        );
+        assert!(parse(Source::Typeracer, "").is_err());
+    }
+}
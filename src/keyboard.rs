@@ -0,0 +1,545 @@
+use crate::thok::{Input, Outcome};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::Path,
+};
+
+/// names accepted by `KeyboardLayout::builtin`
+pub const BUILTIN_LAYOUTS: [&str; 5] = ["qwerty", "dvorak", "colemak", "colemak-dh", "workman"];
+
+/// Unshifted number row + three letter rows of each built-in layout, in
+/// physical (ANSI) key order.
+const QWERTY: [&str; 4] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+const DVORAK: [&str; 4] = [
+    "`1234567890[]",
+    "',.pyfgcrl/=\\",
+    "aoeuidhtns-",
+    ";qjkxbmwvz",
+];
+const COLEMAK: [&str; 4] = [
+    "`1234567890-=",
+    "qwfpgjluy;[]\\",
+    "arstdhneio'",
+    "zxcvbkm,./",
+];
+const COLEMAK_DH: [&str; 4] = [
+    "`1234567890-=",
+    "qwfpbjluy;[]\\",
+    "arstgmneio'",
+    "zxcdvkh,./",
+];
+const WORKMAN: [&str; 4] = [
+    "`1234567890-=",
+    "qdrwbjfup;[]\\",
+    "ashtgyneoi'",
+    "zxmcvkl,./",
+];
+
+/// row of the space bar in `KeyPos`
+pub const SPACE_ROW: usize = 4;
+
+use Finger::*;
+
+/// Which finger presses each physical key under standard touch typing.
+const FINGERS: [&[Finger]; 4] = [
+    &[
+        LeftPinky,
+        LeftPinky,
+        LeftRing,
+        LeftMiddle,
+        LeftIndex,
+        LeftIndex,
+        RightIndex,
+        RightIndex,
+        RightMiddle,
+        RightRing,
+        RightPinky,
+        RightPinky,
+        RightPinky,
+    ],
+    &[
+        LeftPinky,
+        LeftRing,
+        LeftMiddle,
+        LeftIndex,
+        LeftIndex,
+        RightIndex,
+        RightIndex,
+        RightMiddle,
+        RightRing,
+        RightPinky,
+        RightPinky,
+        RightPinky,
+        RightPinky,
+    ],
+    &[
+        LeftPinky,
+        LeftRing,
+        LeftMiddle,
+        LeftIndex,
+        LeftIndex,
+        RightIndex,
+        RightIndex,
+        RightMiddle,
+        RightRing,
+        RightPinky,
+        RightPinky,
+    ],
+    &[
+        LeftPinky,
+        LeftRing,
+        LeftMiddle,
+        LeftIndex,
+        LeftIndex,
+        RightIndex,
+        RightIndex,
+        RightMiddle,
+        RightRing,
+        RightPinky,
+    ],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    Thumb,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    /// None for the thumb, which can sit on either side of the space bar.
+    pub fn hand(self) -> Option<Hand> {
+        match self {
+            LeftPinky | LeftRing | LeftMiddle | LeftIndex => Some(Hand::Left),
+            Thumb => None,
+            RightIndex | RightMiddle | RightRing | RightPinky => Some(Hand::Right),
+        }
+    }
+
+    pub fn short_name(self) -> &'static str {
+        match self {
+            LeftPinky => "lp",
+            LeftRing => "lr",
+            LeftMiddle => "lm",
+            LeftIndex => "li",
+            Thumb => "th",
+            RightIndex => "ri",
+            RightMiddle => "rm",
+            RightRing => "rr",
+            RightPinky => "rp",
+        }
+    }
+}
+
+/// A physical key: row 0 is the number row, `SPACE_ROW` the space bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyPos {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl KeyPos {
+    pub fn finger(self) -> Finger {
+        if self.row == SPACE_ROW {
+            return Thumb;
+        }
+        FINGERS[self.row][self.col]
+    }
+}
+
+/// What it takes to type a character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keystroke {
+    pub pos: KeyPos,
+    pub shift: bool,
+}
+
+/// Maps characters to physical keys. Custom layouts are JSON files with the
+/// same shape: `rows` holds the unshifted number row and three letter rows,
+/// `shifted` optionally overrides the US-style shifted characters.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeyboardLayout {
+    pub name: String,
+    rows: Vec<String>,
+    #[serde(default)]
+    shifted: Option<Vec<String>>,
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::builtin("qwerty").unwrap()
+    }
+}
+
+/// Shifted character on a US keyboard.
+fn us_shift(c: char) -> char {
+    const UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+    const SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+    match UNSHIFTED.chars().position(|u| u == c) {
+        Some(i) => SHIFTED.chars().nth(i).unwrap(),
+        None => c.to_ascii_uppercase(),
+    }
+}
+
+impl KeyboardLayout {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let rows = match name {
+            "qwerty" => QWERTY,
+            "dvorak" => DVORAK,
+            "colemak" => COLEMAK,
+            "colemak-dh" => COLEMAK_DH,
+            "workman" => WORKMAN,
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            rows: rows.iter().map(|r| r.to_string()).collect(),
+            shifted: None,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let layout: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// A built-in layout by name, otherwise a custom layout file.
+    pub fn load(name_or_path: &str) -> Result<Self, Box<dyn Error>> {
+        match Self::builtin(name_or_path) {
+            Some(layout) => Ok(layout),
+            None => Self::from_file(Path::new(name_or_path)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let check = |rows: &[String]| {
+            if rows.len() != FINGERS.len() {
+                return Err(format!("expected {} rows", FINGERS.len()));
+            }
+            for (i, row) in rows.iter().enumerate() {
+                if row.chars().count() > FINGERS[i].len() {
+                    return Err(format!("row {} has more than {} keys", i, FINGERS[i].len()));
+                }
+            }
+            Ok(())
+        };
+        check(&self.rows)?;
+        if let Some(shifted) = &self.shifted {
+            check(shifted)?;
+        }
+        Ok(())
+    }
+
+    /// Character produced by the key at `pos`.
+    pub fn char_at(&self, pos: KeyPos, shift: bool) -> Option<char> {
+        if pos.row == SPACE_ROW {
+            return Some(' ');
+        }
+        match (&self.shifted, shift) {
+            (Some(shifted), true) => shifted.get(pos.row)?.chars().nth(pos.col),
+            (None, true) => self.rows.get(pos.row)?.chars().nth(pos.col).map(us_shift),
+            (_, false) => self.rows.get(pos.row)?.chars().nth(pos.col),
+        }
+    }
+
+    /// Key (and shift state) that types `c`, None if `c` isn't on the layout.
+    pub fn locate(&self, c: char) -> Option<Keystroke> {
+        if c == ' ' {
+            return Some(Keystroke {
+                pos: KeyPos {
+                    row: SPACE_ROW,
+                    col: 0,
+                },
+                shift: false,
+            });
+        }
+        for (row, keys) in self.rows.iter().enumerate() {
+            for col in 0..keys.chars().count() {
+                let pos = KeyPos { row, col };
+                for shift in [false, true] {
+                    if self.char_at(pos, shift) == Some(c) {
+                        return Some(Keystroke { pos, shift });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// The char this layout produces for the key that types `c` on
+    /// `physical`; `c` itself if `physical` has no such key.
+    pub fn emulate(&self, physical: &KeyboardLayout, c: char) -> char {
+        physical
+            .locate(c)
+            .and_then(|ks| self.char_at(ks.pos, ks.shift))
+            .unwrap_or(c)
+    }
+
+    /// Key labels per row, for drawing the layout.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<(KeyPos, char)>> + '_ {
+        self.rows.iter().enumerate().map(|(row, keys)| {
+            keys.chars()
+                .enumerate()
+                .map(|(col, c)| (KeyPos { row, col }, c))
+                .collect()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KeyCount {
+    pub hits: usize,
+    pub errors: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FingerCount {
+    pub keystrokes: usize,
+    pub errors: usize,
+    /// keystrokes that followed another, so took a measurable time
+    pub timed: usize,
+    /// time spent reaching each of the `timed` keystrokes from the previous key
+    pub total_ms: f64,
+}
+
+impl FingerCount {
+    /// Average time per keystroke, leaving out the first of a test.
+    pub fn avg_ms(&self) -> Option<f64> {
+        if self.timed == 0 {
+            None
+        } else {
+            Some(self.total_ms / self.timed as f64)
+        }
+    }
+}
+
+/// Per-key, per-finger and bigram statistics of a finished test.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyboardStats {
+    /// keyed by the key of the *expected* char, so misses show where they
+    /// should have landed
+    pub keys: HashMap<KeyPos, KeyCount>,
+    pub fingers: BTreeMap<Finger, FingerCount>,
+    /// consecutive keystrokes made with hands (the space bar breaks a pair)
+    pub bigrams: usize,
+    /// bigrams on two different keys typed by the same finger
+    pub same_finger_bigrams: usize,
+    /// bigrams switching from one hand to the other
+    pub hand_alternations: usize,
+}
+
+impl KeyboardStats {
+    pub fn same_finger_pct(&self) -> f64 {
+        pct(self.same_finger_bigrams, self.bigrams)
+    }
+
+    pub fn alternation_pct(&self) -> f64 {
+        pct(self.hand_alternations, self.bigrams)
+    }
+}
+
+fn pct(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        (n as f64 / d as f64 * 100.0).round()
+    }
+}
+
+pub(crate) fn analyze(
+    layout: &KeyboardLayout,
+    prompt_chars: &[char],
+    input: &[Input],
+) -> KeyboardStats {
+    let mut stats = KeyboardStats::default();
+    let mut prev: Option<(&Input, Option<Keystroke>)> = None;
+
+    for (idx, i) in input.iter().enumerate() {
+        let error = i.outcome == Outcome::Incorrect;
+        let typed = layout.locate(i.char);
+
+        if let Some(expected) = prompt_chars.get(idx).and_then(|&c| layout.locate(c)) {
+            let count = stats.keys.entry(expected.pos).or_default();
+            count.hits += 1;
+            if error {
+                count.errors += 1;
+            }
+        }
+
+        if let Some(ks) = typed {
+            let count = stats.fingers.entry(ks.pos.finger()).or_default();
+            count.keystrokes += 1;
+            if error {
+                count.errors += 1;
+            }
+            if let Some((p, _)) = prev {
+                if let Some(elapsed) = i.timestamp.checked_duration_since(p.timestamp) {
+                    count.timed += 1;
+                    count.total_ms += elapsed.as_secs_f64() * 1000.0;
+                }
+            }
+        }
+
+        if let (Some((_, Some(a))), Some(b)) = (prev, typed) {
+            let (fa, fb) = (a.pos.finger(), b.pos.finger());
+            if let (Some(ha), Some(hb)) = (fa.hand(), fb.hand()) {
+                stats.bigrams += 1;
+                if fa == fb && a.pos != b.pos {
+                    stats.same_finger_bigrams += 1;
+                }
+                if ha != hb {
+                    stats.hand_alternations += 1;
+                }
+            }
+        }
+
+        prev = Some((i, typed));
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn inputs(prompt: &str, typed: &str, step_ms: u64) -> Vec<Input> {
+        let start = Instant::now();
+        typed
+            .chars()
+            .zip(prompt.chars())
+            .enumerate()
+            .map(|(i, (c, e))| Input {
+                char: c,
+                outcome: if c == e {
+                    Outcome::Correct
+                } else {
+                    Outcome::Incorrect
+                },
+                timestamp: start + Duration::from_millis(step_ms * i as u64),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn locates_letters_and_shifted_chars() {
+        let qwerty = KeyboardLayout::default();
+        let f = qwerty.locate('f').unwrap();
+        assert_eq!(f.pos, KeyPos { row: 2, col: 3 });
+        assert_eq!(f.pos.finger(), LeftIndex);
+        assert!(qwerty.locate('F').unwrap().shift);
+        assert_eq!(qwerty.locate('?').unwrap().pos, KeyPos { row: 3, col: 9 });
+        assert_eq!(qwerty.locate(' ').unwrap().pos.finger(), Thumb);
+        assert_eq!(qwerty.locate('é'), None);
+    }
+
+    #[test]
+    fn same_char_different_keys_across_layouts() {
+        let qwerty = KeyboardLayout::default();
+        for name in BUILTIN_LAYOUTS {
+            let layout = KeyboardLayout::builtin(name).unwrap();
+            assert!(layout.validate().is_ok(), "{name}");
+            // every letter is typeable on every layout
+            assert!(('a'..='z').all(|c| layout.locate(c).is_some()), "{name}");
+        }
+        let colemak = KeyboardLayout::builtin("colemak").unwrap();
+        let qwerty_s = qwerty.locate('s').unwrap().pos;
+        assert_eq!(colemak.char_at(qwerty_s, false), Some('r'));
+        assert_eq!(
+            KeyboardLayout::builtin("dvorak")
+                .unwrap()
+                .char_at(qwerty_s, true),
+            Some('O')
+        );
+    }
+
+    #[test]
+    fn emulates_layout_from_qwerty_positions() {
+        let qwerty = KeyboardLayout::default();
+        let colemak = KeyboardLayout::builtin("colemak").unwrap();
+        let typed: String = "asdf jkl;"
+            .chars()
+            .map(|c| colemak.emulate(&qwerty, c))
+            .collect();
+        assert_eq!(typed, "arst neio");
+        assert_eq!(colemak.emulate(&qwerty, 'D'), 'S');
+        assert_eq!(colemak.emulate(&qwerty, 'é'), 'é');
+
+        let dvorak = KeyboardLayout::builtin("dvorak").unwrap();
+        assert_eq!(dvorak.emulate(&qwerty, 'q'), '\'');
+        assert_eq!(dvorak.emulate(&qwerty, 'Z'), ':');
+        assert_eq!(qwerty.emulate(&qwerty, 'x'), 'x');
+    }
+
+    #[test]
+    fn bigram_stats() {
+        // "de" is a same-finger bigram on qwerty (left middle), "fj" alternates
+        let qwerty = KeyboardLayout::default();
+        let stats = analyze(
+            &qwerty,
+            &['d', 'e', ' ', 'f', 'j'],
+            &inputs("de fj", "de fj", 100),
+        );
+        assert_eq!(stats.bigrams, 2);
+        assert_eq!(stats.same_finger_bigrams, 1);
+        assert_eq!(stats.hand_alternations, 1);
+        assert_eq!(stats.same_finger_pct(), 50.0);
+
+        let colemak = KeyboardLayout::builtin("colemak").unwrap();
+        let stats = analyze(
+            &colemak,
+            &['d', 'e', ' ', 'f', 'j'],
+            &inputs("de fj", "de fj", 100),
+        );
+        assert_eq!(stats.same_finger_bigrams, 0);
+    }
+
+    #[test]
+    fn per_key_errors_and_finger_timing() {
+        let qwerty = KeyboardLayout::default();
+        let prompt = ['a', 'a', 'a'];
+        let stats = analyze(&qwerty, &prompt, &inputs("aaa", "aqa", 200));
+        let a = qwerty.locate('a').unwrap().pos;
+        assert_eq!(stats.keys[&a], KeyCount { hits: 3, errors: 1 });
+        let pinky = stats.fingers[&LeftPinky];
+        assert_eq!(pinky.keystrokes, 3);
+        assert_eq!(pinky.errors, 1);
+        // the first keystroke has no previous key, the other two took 200ms
+        assert_eq!((pinky.timed, pinky.total_ms), (2, 400.0));
+        assert_eq!(pinky.avg_ms(), Some(200.0));
+    }
+
+    #[test]
+    fn custom_layout_is_validated() {
+        let bad: KeyboardLayout =
+            serde_json::from_str(r#"{"name": "bad", "rows": ["abc"]}"#).unwrap();
+        assert!(bad.validate().is_err());
+        let good: KeyboardLayout = serde_json::from_str(
+            r#"{"name": "good", "rows": ["`1234567890-=", "qwertyuiop", "asdfghjkl", "zxcvbnm"]}"#,
+        )
+        .unwrap();
+        assert!(good.validate().is_ok());
+        assert_eq!(good.locate('Q').unwrap().pos, KeyPos { row: 1, col: 0 });
+    }
+}
@@ -0,0 +1,179 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::mem;
+
+/// made-up words are cut off at this many chars, so a corpus with no spaces
+/// (a single word, CJK text) still yields words
+const MAX_WORD_CHARS: usize = 15;
+
+/// What a single step of the chain emits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// pronounceable made-up words, one letter at a time
+    Char,
+    /// real words strung together in a plausible order
+    Word,
+}
+
+/// An order-N Markov model over the characters or words of a corpus.
+#[derive(Clone, Debug)]
+pub struct Markov {
+    unit: Unit,
+    /// every state (the last `order` tokens) and the tokens seen after it
+    transitions: HashMap<Vec<String>, Vec<String>>,
+    /// states that begin a word, used to (re)start generation
+    starts: Vec<Vec<String>>,
+}
+
+impl Markov {
+    /// Trains on `corpus` with runs of whitespace collapsed to single spaces.
+    /// Returns None if the corpus is too short to yield a single state.
+    pub fn train(corpus: &str, unit: Unit, order: usize) -> Option<Self> {
+        let order = order.max(1);
+        let tokens: Vec<String> = match unit {
+            Unit::Char => corpus
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .chars()
+                .map(String::from)
+                .collect(),
+            Unit::Word => corpus.split_whitespace().map(String::from).collect(),
+        };
+
+        let mut transitions: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+        let mut starts = Vec::new();
+
+        for (i, window) in tokens.windows(order + 1).enumerate() {
+            let state = window[..order].to_vec();
+            let at_word_start = match unit {
+                Unit::Char => i == 0 || tokens[i - 1] == " ",
+                Unit::Word => true,
+            };
+            if at_word_start && state[0] != " " {
+                starts.push(state.clone());
+            }
+            transitions
+                .entry(state)
+                .or_default()
+                .push(window[order].clone());
+        }
+
+        if starts.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            unit,
+            transitions,
+            starts,
+        })
+    }
+
+    /// `num` words of generated text. A state with no successors (the end
+    /// of the corpus) restarts the chain from a fresh word, as does a made-up
+    /// word reaching `MAX_WORD_CHARS`.
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R, num: usize) -> Vec<String> {
+        let mut words = Vec::with_capacity(num);
+        let mut word = String::new();
+        let mut state: Vec<String> = vec![];
+
+        while words.len() < num {
+            let next = self
+                .transitions
+                .get(&state)
+                .and_then(|successors| successors.choose(rng));
+
+            let tokens = match next {
+                Some(token) => {
+                    state.remove(0);
+                    state.push(token.clone());
+                    vec![token.clone()]
+                }
+                None => {
+                    // a restart always begins a new word
+                    if !word.is_empty() {
+                        words.push(mem::take(&mut word));
+                    }
+                    state = self.starts.choose(rng).unwrap().clone();
+                    state.clone()
+                }
+            };
+
+            for token in tokens {
+                match self.unit {
+                    Unit::Word => words.push(token),
+                    Unit::Char if token == " " => {
+                        if !word.is_empty() {
+                            words.push(mem::take(&mut word));
+                        }
+                    }
+                    Unit::Char => {
+                        word.push_str(&token);
+                        if word.chars().count() >= MAX_WORD_CHARS {
+                            words.push(mem::take(&mut word));
+                            // no state is empty, so this restarts the chain
+                            state.clear();
+                        }
+                    }
+                }
+            }
+        }
+
+        words.truncate(num);
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const CORPUS: &str = "the cat sat on the mat and the dog sat on the log";
+
+    #[test]
+    fn generates_exact_word_count() {
+        for unit in [Unit::Char, Unit::Word] {
+            let markov = Markov::train(CORPUS, unit, 2).unwrap();
+            let words = markov.generate(&mut StdRng::seed_from_u64(1), 40);
+            assert_eq!(words.len(), 40, "{unit:?}");
+        }
+    }
+
+    #[test]
+    fn word_model_only_emits_corpus_words() {
+        let markov = Markov::train(CORPUS, Unit::Word, 1).unwrap();
+        let words = markov.generate(&mut StdRng::seed_from_u64(2), 30);
+        assert!(words.iter().all(|w| CORPUS.split(' ').any(|c| c == w)));
+    }
+
+    #[test]
+    fn char_model_follows_corpus_transitions() {
+        let markov = Markov::train(CORPUS, Unit::Char, 3).unwrap();
+        let text = markov.generate(&mut StdRng::seed_from_u64(3), 20).join(" ");
+        // every 4-gram of the output that doesn't span a restart was seen in
+        // training; with this corpus none of them can contain "tt"
+        assert!(!text.contains("tt"));
+        assert!(text.chars().all(|c| c == ' ' || CORPUS.contains(c)));
+    }
+
+    #[test]
+    fn corpus_without_spaces_still_yields_words() {
+        for corpus in ["mississippi", "aaaa", "天地玄黄宇宙洪荒"] {
+            let markov = Markov::train(corpus, Unit::Char, 2).unwrap();
+            let words = markov.generate(&mut StdRng::seed_from_u64(4), 10);
+            assert_eq!(words.len(), 10, "{corpus}");
+            assert!(words
+                .iter()
+                .all(|w| (1..=MAX_WORD_CHARS).contains(&w.chars().count())));
+        }
+    }
+
+    #[test]
+    fn corpus_too_short_is_rejected() {
+        assert!(Markov::train("hi", Unit::Char, 3).is_none());
+        assert!(Markov::train("", Unit::Word, 1).is_none());
+    }
+}
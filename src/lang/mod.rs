@@ -1,11 +1,13 @@
+pub mod markov;
+
 use cgisf_lib::cgisf;
-use rand::seq::SliceRandom;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
 use serde::Deserialize;
 use serde_json::from_str;
 
 use include_dir::{include_dir, Dir};
 use rand::Rng;
-use std::error::Error;
 
 static LANG_DIR: Dir = include_dir!("src/lang");
 
@@ -14,12 +16,50 @@ static LANG_DIR: Dir = include_dir!("src/lang");
 pub struct Language {
     name: String,
     size: u32,
+    /// whether `words` is sorted most-common-first, which is what
+    /// frequency-weighted sampling relies on
+    #[serde(default)]
+    frequency_ordered: bool,
     words: Vec<String>,
 }
 
+/// How words are drawn from a language's list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+    /// favor common words (Zipf weights by frequency rank); ignored for lists
+    /// that aren't frequency ordered
+    pub weighted: bool,
+    /// once a prompt needs more words than the list holds, a word may only
+    /// reappear after this many other words
+    pub min_gap: usize,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            weighted: false,
+            min_gap: 10,
+        }
+    }
+}
+
 impl Language {
-    pub fn new(file_name: String) -> Self {
-        read_language_from_file(format!("{}.json", file_name)).unwrap()
+    /// The bundled language `file_name`, e.g. "english1k", if there is one.
+    pub fn new(file_name: String) -> Option<Self> {
+        read_language_from_file(format!("{}.json", file_name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether `Sampling::weighted` has any effect on this list.
+    pub fn frequency_ordered(&self) -> bool {
+        self.frequency_ordered
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
     }
 
     pub fn get_random_sentence(&self, num: usize) -> (Vec<String>, usize) {
@@ -45,23 +85,156 @@ impl Language {
         (vec, word_count)
     }
 
-    pub fn get_random(&self, num: usize) -> Vec<String> {
-        let mut rng = &mut rand::thread_rng();
+    pub fn get_random(&self, num: usize, sampling: &Sampling) -> Vec<String> {
+        self.get_random_with(&mut rand::thread_rng(), num, sampling)
+    }
+
+    /// Exactly `num` words drawn from `rng` per `sampling`; a seeded rng
+    /// always yields the same prompt. Words never repeat while `num` fits in
+    /// the list, otherwise repeats are spaced at least `min_gap` apart.
+    pub fn get_random_with<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        num: usize,
+        sampling: &Sampling,
+    ) -> Vec<String> {
+        let len = self.words.len();
+        if len == 0 {
+            return vec![];
+        }
+
+        // how many of the most recent picks are off limits for the next one
+        let window = if num <= len {
+            num
+        } else {
+            sampling.min_gap.min(len - 1)
+        };
+
+        let weights = (0..len)
+            .map(|rank| {
+                if sampling.weighted && self.frequency_ordered {
+                    1.0 / (rank + 1) as f64
+                } else {
+                    1.0
+                }
+            })
+            .collect::<Vec<f64>>();
+
+        let mut picks: Vec<usize> = Vec::with_capacity(num);
+        let mut blocked = vec![false; len];
+
+        for _ in 0..num {
+            let eligible = weights
+                .iter()
+                .zip(&blocked)
+                .map(|(&w, &b)| if b { 0.0 } else { w });
+            let idx = WeightedIndex::new(eligible)
+                .expect("window leaves at least one eligible word")
+                .sample(rng);
 
-        self.words.choose_multiple(&mut rng, num).cloned().collect()
+            picks.push(idx);
+            blocked[idx] = true;
+            if picks.len() > window {
+                blocked[picks[picks.len() - 1 - window]] = false;
+            }
+        }
+
+        picks.into_iter().map(|i| self.words[i].clone()).collect()
     }
 }
 
-fn read_language_from_file(file_name: String) -> Result<Language, Box<dyn Error>> {
-    let file = LANG_DIR
-        .get_file(file_name)
-        .expect("Language file not found");
+/// Prose bundled with thokr for the Markov prompt generator.
+pub fn builtin_corpus() -> &'static str {
+    LANG_DIR
+        .get_file("corpus.txt")
+        .and_then(|file| file.contents_utf8())
+        .expect("Built-in corpus not found")
+}
+
+fn read_language_from_file(file_name: String) -> Option<Language> {
+    let file = LANG_DIR.get_file(file_name)?;
+    from_str(file.contents_utf8()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn language(words: &[&str], frequency_ordered: bool) -> Language {
+        Language {
+            name: "test".to_string(),
+            size: words.len() as u32,
+            frequency_ordered,
+            words: words.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn unknown_languages_are_none() {
+        assert!(Language::new("klingon".to_string()).is_none());
+        assert!(Language::new("english".to_string()).is_some());
+    }
+
+    #[test]
+    fn seeded_sampling_is_reproducible() {
+        let lang = Language::new("english".to_string()).unwrap();
+        let a = lang.get_random_with(&mut StdRng::seed_from_u64(7), 15, &Sampling::default());
+        let b = lang.get_random_with(&mut StdRng::seed_from_u64(7), 15, &Sampling::default());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn no_repeats_when_list_is_large_enough() {
+        let lang = Language::new("english".to_string()).unwrap();
+        let words = lang.get_random_with(&mut StdRng::seed_from_u64(1), 200, &Sampling::default());
+        assert_eq!(words.len(), 200);
+        let mut unique = words.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 200);
+    }
 
-    let file_as_str = file
-        .contents_utf8()
-        .expect("Unable to interpret file as a string");
+    #[test]
+    fn more_words_than_list_repeats_with_gap() {
+        let lang = language(&["a", "b", "c", "d", "e", "f"], false);
+        let sampling = Sampling {
+            weighted: false,
+            min_gap: 3,
+        };
+        let words = lang.get_random_with(&mut StdRng::seed_from_u64(3), 500, &sampling);
+        assert_eq!(words.len(), 500);
+        for (i, w) in words.iter().enumerate() {
+            let next = words[i + 1..].iter().take(3);
+            assert!(next.into_iter().all(|n| n != w), "{w} repeated within gap");
+        }
+    }
 
-    let lang = from_str(file_as_str).expect("Unable to deserialize language json");
+    #[test]
+    fn gap_larger_than_list_is_clamped() {
+        let lang = language(&["a", "b"], false);
+        let sampling = Sampling {
+            weighted: true,
+            min_gap: 50,
+        };
+        let words = lang.get_random_with(&mut StdRng::seed_from_u64(3), 6, &sampling);
+        assert_eq!(words.len(), 6);
+        assert!(words.windows(2).all(|w| w[0] != w[1]));
+    }
 
-    Ok(lang)
+    #[test]
+    fn weighted_sampling_favors_common_words() {
+        let lang = Language::new("english1k".to_string()).unwrap();
+        let sampling = Sampling {
+            weighted: true,
+            min_gap: 0,
+        };
+        let words = lang.get_random_with(&mut StdRng::seed_from_u64(5), 2000, &sampling);
+        let top = words
+            .iter()
+            .filter(|w| lang.words[..100].contains(w))
+            .count();
+        // uniform would put ~10% of picks in the top 100
+        assert!(top > words.len() / 2, "only {top} common words");
+    }
 }
@@ -0,0 +1,317 @@
+use crate::history;
+use crate::keyboard::{KeyPos, KeyboardLayout};
+use crate::lang::Language;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// One step of the curriculum. Keys are physical positions so every lesson
+/// works on whichever keyboard layout the user types on.
+#[derive(Clone, Copy, Debug)]
+pub struct Lesson {
+    pub name: &'static str,
+    /// (row, first col, last col) of the keys this lesson adds
+    new_keys: &'static [(usize, usize, usize)],
+    /// whether this lesson adds the shifted characters of every key so far
+    adds_shift: bool,
+    pub target_wpm: f64,
+    pub target_accuracy: f64,
+}
+
+pub const LESSONS: [Lesson; 6] = [
+    Lesson {
+        name: "home row: index and middle fingers",
+        new_keys: &[(2, 2, 7)],
+        adds_shift: false,
+        target_wpm: 15.0,
+        target_accuracy: 95.0,
+    },
+    Lesson {
+        name: "home row",
+        new_keys: &[(2, 0, 10)],
+        adds_shift: false,
+        target_wpm: 20.0,
+        target_accuracy: 95.0,
+    },
+    Lesson {
+        name: "top row",
+        new_keys: &[(1, 0, 9)],
+        adds_shift: false,
+        target_wpm: 20.0,
+        target_accuracy: 95.0,
+    },
+    Lesson {
+        name: "bottom row",
+        new_keys: &[(3, 0, 9)],
+        adds_shift: false,
+        target_wpm: 25.0,
+        target_accuracy: 95.0,
+    },
+    Lesson {
+        name: "numbers",
+        new_keys: &[(0, 1, 10)],
+        adds_shift: false,
+        target_wpm: 25.0,
+        target_accuracy: 95.0,
+    },
+    Lesson {
+        name: "symbols and capitals",
+        new_keys: &[(0, 0, 12), (1, 10, 12)],
+        adds_shift: true,
+        target_wpm: 25.0,
+        target_accuracy: 95.0,
+    },
+];
+
+impl Lesson {
+    fn keys(&self, layout: &KeyboardLayout, shift: bool) -> Vec<char> {
+        self.new_keys
+            .iter()
+            .flat_map(|&(row, first, last)| (first..=last).map(move |col| KeyPos { row, col }))
+            .flat_map(|pos| {
+                let mut chars = vec![layout.char_at(pos, false)];
+                if shift {
+                    chars.push(layout.char_at(pos, true));
+                }
+                chars
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn passed(&self, wpm: f64, accuracy: f64) -> bool {
+        wpm >= self.target_wpm && accuracy >= self.target_accuracy
+    }
+}
+
+/// Characters allowed in lesson `idx` and the ones it introduces.
+pub fn key_sets(layout: &KeyboardLayout, idx: usize) -> (Vec<char>, Vec<char>) {
+    let shift = LESSONS[..=idx].iter().any(|l| l.adds_shift);
+    let mut allowed: Vec<char> = LESSONS[..=idx]
+        .iter()
+        .flat_map(|l| l.keys(layout, shift))
+        .collect();
+    allowed.sort_unstable();
+    allowed.dedup();
+
+    let mut introduced = if LESSONS[idx].adds_shift {
+        let earlier = LESSONS[..idx]
+            .iter()
+            .flat_map(|l| l.keys(layout, false))
+            .collect::<Vec<char>>();
+        allowed
+            .iter()
+            .copied()
+            .filter(|c| !earlier.contains(c))
+            .collect()
+    } else {
+        LESSONS[idx].keys(layout, false)
+    };
+    introduced.sort_unstable();
+    introduced.dedup();
+
+    (allowed, introduced)
+}
+
+/// `num` words using only the keys of lesson `idx`: real words where the
+/// dictionary has enough of them, drills of the lesson's new keys otherwise.
+pub fn generate<R: Rng + ?Sized>(
+    rng: &mut R,
+    layout: &KeyboardLayout,
+    idx: usize,
+    num: usize,
+) -> Vec<String> {
+    let (allowed, introduced) = key_sets(layout, idx);
+
+    let bundled = |name: &str| Language::new(name.to_string()).expect("english is bundled");
+    let mut dictionary = bundled("english10k").words().to_vec();
+    dictionary.extend_from_slice(bundled("english1k").words());
+    let mut words = dictionary
+        .into_iter()
+        .filter(|w| w.chars().all(|c| allowed.contains(&c)))
+        .collect::<Vec<String>>();
+    words.sort_unstable();
+    words.dedup();
+
+    (0..num)
+        .map(|_| {
+            // fall back to drills entirely when only a handful of words fit
+            if words.len() >= 10 && rng.gen_bool(0.5) {
+                words.choose(rng).unwrap().clone()
+            } else {
+                (0..rng.gen_range(2..=5))
+                    .map(|_| {
+                        let pool = if rng.gen_bool(0.5) {
+                            &introduced
+                        } else {
+                            &allowed
+                        };
+                        *pool.choose(rng).unwrap()
+                    })
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LessonResult {
+    pub wpm: f64,
+    pub accuracy: f64,
+}
+
+/// What the learner has achieved so far, persisted in the data dir.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// highest lesson that can be practiced
+    pub unlocked: usize,
+    /// best wpm run per lesson
+    pub best: BTreeMap<usize, LessonResult>,
+}
+
+/// What the results screen shows after a lesson.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LessonSummary {
+    pub lesson: usize,
+    pub passed: bool,
+    /// set when this run unlocked a new lesson
+    pub unlocked: Option<usize>,
+}
+
+impl Progress {
+    /// The progress saved in `dir`, or none yet if nothing was saved.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        match std::fs::read_to_string(dir.join("learn.json")) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        history::replace(
+            &dir.join("learn.json"),
+            &serde_json::to_string_pretty(self)?,
+        )
+    }
+
+    /// Lesson to practice next.
+    pub fn current(&self) -> usize {
+        self.unlocked.min(LESSONS.len() - 1)
+    }
+
+    /// Records a finished run of lesson `idx`, unlocking the next lesson when
+    /// it met the targets.
+    pub fn record(&mut self, idx: usize, result: LessonResult) -> LessonSummary {
+        let best = self.best.entry(idx).or_default();
+        if result.wpm > best.wpm {
+            *best = result;
+        }
+
+        let passed = LESSONS[idx].passed(result.wpm, result.accuracy);
+        let unlocked = if passed && idx == self.unlocked && idx + 1 < LESSONS.len() {
+            self.unlocked += 1;
+            Some(self.unlocked)
+        } else {
+            None
+        };
+
+        LessonSummary {
+            lesson: idx,
+            passed,
+            unlocked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn key_sets_grow_with_each_lesson() {
+        let qwerty = KeyboardLayout::default();
+        let (allowed, introduced) = key_sets(&qwerty, 0);
+        assert_eq!(allowed, vec!['d', 'f', 'g', 'h', 'j', 'k']);
+        assert_eq!(introduced, allowed);
+
+        let (allowed, introduced) = key_sets(&qwerty, 1);
+        assert!(allowed.contains(&'a') && allowed.contains(&';'));
+        assert!(!allowed.contains(&'q'));
+        assert!(introduced.contains(&'f'));
+
+        let (allowed, introduced) = key_sets(&qwerty, 5);
+        assert!(allowed.contains(&'Q') && allowed.contains(&'!') && allowed.contains(&'['));
+        assert!(!introduced.contains(&'q'));
+    }
+
+    #[test]
+    fn lesson_keys_follow_the_layout() {
+        let colemak = KeyboardLayout::builtin("colemak").unwrap();
+        let (allowed, _) = key_sets(&colemak, 0);
+        assert_eq!(allowed, vec!['d', 'e', 'h', 'n', 's', 't']);
+    }
+
+    #[test]
+    fn generated_words_only_use_allowed_keys() {
+        let qwerty = KeyboardLayout::default();
+        for idx in 0..LESSONS.len() {
+            let (allowed, _) = key_sets(&qwerty, idx);
+            let words = generate(&mut StdRng::seed_from_u64(idx as u64), &qwerty, idx, 30);
+            assert_eq!(words.len(), 30);
+            assert!(
+                words
+                    .iter()
+                    .all(|w| w.chars().all(|c| allowed.contains(&c))),
+                "lesson {idx}: {words:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn passing_unlocks_the_next_lesson_once() {
+        let mut progress = Progress::default();
+        let slow = LessonResult {
+            wpm: 5.0,
+            accuracy: 100.0,
+        };
+        let fast = LessonResult {
+            wpm: 40.0,
+            accuracy: 98.0,
+        };
+
+        assert_eq!(progress.record(0, slow).unlocked, None);
+        assert_eq!(progress.current(), 0);
+        assert_eq!(progress.record(0, fast).unlocked, Some(1));
+        assert_eq!(progress.current(), 1);
+        // replaying an earlier lesson doesn't unlock anything new
+        assert_eq!(progress.record(0, fast).unlocked, None);
+        assert_eq!(progress.best[&0], fast);
+    }
+
+    #[test]
+    fn progress_survives_a_round_trip() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        assert_eq!(Progress::load(dir).unwrap(), Progress::default());
+
+        let mut progress = Progress::default();
+        progress.record(
+            0,
+            LessonResult {
+                wpm: 100.0,
+                accuracy: 100.0,
+            },
+        );
+        progress.save(dir).unwrap();
+        assert_eq!(Progress::load(dir).unwrap(), progress);
+
+        std::fs::write(dir.join("learn.json"), "{\"unlocked\": 3, \"be").unwrap();
+        assert!(Progress::load(dir).is_err());
+    }
+}
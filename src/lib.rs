@@ -0,0 +1,77 @@
+//! The typing test behind the `thokr` binary, for embedding one in another
+//! [ratatui](https://ratatui.rs) app.
+//!
+//! - [`thok::Thok`] is a single test: feed it keystrokes with
+//!   [`write`](thok::Thok::write) and [`backspace`](thok::Thok::backspace),
+//!   redraw it after [`next_tick`](thok::Thok::next_tick) when no key comes
+//!   in for timed tests or a pace caret, and call
+//!   [`calc_results`](thok::Thok::calc_results) once it
+//!   [`has_finished`](thok::Thok::has_finished).
+//! - `&Thok` is a ratatui [`Widget`](ratatui::widgets::Widget) that draws the
+//!   prompt while typing and the results afterwards; [`ui::cursor_screen_position`]
+//!   tells where to put the terminal cursor.
+//! - [`clock`] is where a test gets the time from; give
+//!   [`Thok::with_clock`](thok::Thok::with_clock) a [`clock::FakeClock`] to
+//!   drive one with exact timings.
+//! - [`lang`] and [`learn`] generate prompts: word lists, sentences, Markov
+//!   text and touch-typing drills.
+//! - [`event::EventSource`] is where keys come from: the terminal, or a
+//!   [`event::Replay`] on a fake clock for headless runs.
+//! - [`simulate`] plays a scripted keystroke timeline into a test, no
+//!   terminal needed.
+//! - [`layout`] wraps a prompt into lines the way the widget does.
+//! - [`keyboard`] maps keys to fingers for the finger analytics and heatmap.
+//!
+//! ```
+//! use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+//! use thokr::thok::Thok;
+//!
+//! let mut thok = Thok::new(String::from("hello world"), 2, None);
+//! for c in "hello world".chars() {
+//!     thok.write(c);
+//! }
+//! assert!(thok.has_finished());
+//! thok.calc_results();
+//! assert_eq!(thok.accuracy(), 100.0);
+//!
+//! let area = Rect::new(0, 0, 80, 24);
+//! let mut buf = Buffer::empty(area);
+//! (&thok).render(area, &mut buf);
+//! ```
+//!
+//! Anything hidden from these docs (the history and data files, racing,
+//! watching and the like) is only public for the binary's sake and carries no
+//! stability promise.
+
+pub mod clock;
+pub mod event;
+pub mod keyboard;
+pub mod lang;
+pub mod layout;
+pub mod learn;
+pub mod simulate;
+pub mod thok;
+pub mod ui;
+
+#[doc(hidden)]
+pub mod card;
+#[doc(hidden)]
+pub mod daily;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod paths;
+#[doc(hidden)]
+pub mod race;
+#[doc(hidden)]
+pub mod review;
+#[doc(hidden)]
+pub mod spectate;
+pub(crate) mod util;
+
+/// How often (in ms) the countdown and pace caret of a running test move.
+pub const TICK_RATE_MS: u64 = 100;
@@ -1,34 +1,52 @@
-mod lang;
-mod layout;
-mod thok;
-mod ui;
-mod util;
-
-use crate::{lang::Language, thok::Thok};
+use chrono::NaiveDate;
 use clap::error::ErrorKind;
-use clap::{CommandFactory, Parser, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use rand::{rngs::StdRng, SeedableRng};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
     crossterm::{
         cursor::SetCursorStyle,
-        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        event::{Event, KeyCode, KeyEventKind, KeyModifiers},
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         tty::IsTty,
     },
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Clear, Paragraph, Widget},
     Frame, Terminal,
 };
+use serde::Serialize;
 use std::{
+    env,
     error::Error,
-    io::{self, stdin},
-    sync::mpsc,
+    fs,
+    io::{self, stdin, Write},
+    net::{IpAddr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::Arc,
     thread,
-    time::Duration,
+};
+use thokr::{
+    card,
+    clock::{Clock, FakeClock, SystemClock},
+    daily,
+    event::{self, EventSource},
+    export, history, import, keyboard,
+    keyboard::KeyboardLayout,
+    lang,
+    lang::{
+        markov::{Markov, Unit},
+        Language, Sampling,
+    },
+    learn, paths, race, review, simulate, spectate,
+    thok::{Results, ResultsView, Thok},
+    ui,
 };
 use webbrowser::Browser;
 
-const TICK_RATE_MS: u64 = 100;
-
 /// sleek typing tui with visualized results and historical logging
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
@@ -56,6 +74,207 @@ pub struct Cli {
     /// ghost caret pacing at this WPM to race against
     #[arg(long)]
     pace: Option<u16>,
+
+    /// favor common words by weighting them by frequency rank (english and
+    /// english1k only)
+    #[arg(long, conflicts_with_all = ["prompt", "number_of_sentences", "daily", "review", "markov"])]
+    weighted: bool,
+
+    /// minimum number of words between repeats when more words are requested
+    /// than the language has
+    #[arg(long, default_value_t = Sampling::default().min_gap)]
+    repeat_gap: usize,
+
+    /// seed for word selection so the same prompt can be replayed
+    #[arg(long, conflicts_with_all = ["prompt", "number_of_sentences"])]
+    seed: Option<u64>,
+
+    /// today's challenge: the same prompt for everyone on a given (UTC) day
+    #[arg(long, conflicts_with_all = ["prompt", "number_of_sentences", "seed", "number_of_words", "supported_language"])]
+    daily: bool,
+
+    /// generate text with a Markov chain over the characters or words of a corpus
+    #[arg(long, value_enum, conflicts_with_all = ["prompt", "number_of_sentences", "daily"])]
+    markov: Option<MarkovUnit>,
+
+    /// number of preceding characters/words the Markov chain looks at
+    #[arg(long, default_value_t = 3, requires = "markov")]
+    order: usize,
+
+    /// text file to train the Markov chain on instead of the built-in corpus
+    #[arg(long, requires = "markov")]
+    corpus: Option<PathBuf>,
+
+    /// keyboard layout for finger analytics and the heatmap: qwerty, dvorak,
+    /// colemak, colemak-dh, workman or a path to a custom layout file
+    /// [default: the emulated layout, else qwerty]
+    #[arg(short = 'k', long)]
+    keyboard: Option<String>,
+
+    /// practice another layout on a QWERTY keyboard: keys are remapped from
+    /// their QWERTY position to this layout while typing
+    #[arg(long)]
+    emulate_layout: Option<String>,
+
+    /// practice the words you've mistyped that are due for review
+    #[arg(long, conflicts_with_all = ["prompt", "number_of_sentences", "daily", "markov"])]
+    review: bool,
+
+    /// directory for the results history, daily challenges, review queue and
+    /// lesson progress [default: the platform's data dir]
+    #[arg(long, env = "THOKR_DATA_DIR", value_name = "DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// don't save anything: results history, daily challenges, review queue or
+    /// lesson progress (e.g. on shared or demo machines)
+    #[arg(long)]
+    no_history: bool,
+
+    /// print the results of the last finished test as JSON on quitting
+    #[arg(long)]
+    json: bool,
+
+    /// write the results of the last finished test as JSON to this file on
+    /// quitting
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// let others follow your tests live with `thokr watch`, listening on
+    /// this address [default: 127.0.0.1:7879]
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = spectate::DEFAULT_ADDR)]
+    publish: Option<String>,
+
+    /// directory `(e)xport` saves results cards to [default: the platform's
+    /// pictures dir]
+    #[arg(long, env = "THOKR_CARD_DIR", value_name = "DIR")]
+    card_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// guided touch-typing lessons, from the home row to symbols
+    Learn {
+        /// practice this lesson (1-based) instead of the latest unlocked one
+        #[arg(long)]
+        lesson: Option<usize>,
+    },
+    /// write the results history as CSV, JSON or a Markdown/HTML progress
+    /// report with personal bests, weekly averages and per-mode breakdowns
+    Export {
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Markdown)]
+        format: ExportFormat,
+
+        /// only tests taken on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// only tests taken on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+
+        /// file to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// add results exported from another typing trainer to the history
+    Import {
+        /// trainer the file was exported from
+        #[arg(long, value_enum)]
+        from: ImportSource,
+
+        /// exported CSV file
+        file: PathBuf,
+    },
+    /// race others on the LAN: they join with `thokr join`, and everyone
+    /// types the same prompt, made from the usual flags
+    Host {
+        /// port to listen on
+        #[arg(long, default_value_t = race::DEFAULT_PORT)]
+        port: u16,
+
+        /// number of racers to wait for, yourself included
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u16).range(1..))]
+        racers: u16,
+
+        /// name shown to the other racers [default: $USER]
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// join a race started with `thokr host`
+    Join {
+        /// address of the host, e.g. 192.168.1.20 or 192.168.1.20:7878
+        addr: String,
+
+        /// name shown to the other racers [default: $USER]
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// follow the tests of a thokr started with `--publish`, read-only
+    Watch {
+        /// address it publishes on, e.g. 192.168.1.20 or 192.168.1.20:7879
+        #[arg(default_value = spectate::DEFAULT_ADDR)]
+        addr: String,
+    },
+    /// play a scripted keystroke timeline into a test, without a terminal,
+    /// and print the results as JSON; the prompt comes from the usual flags
+    Simulate {
+        /// file with one `<offset ms> <key>` per line, where the key is a
+        /// single char, `space` or `backspace`
+        timeline: PathBuf,
+    },
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+enum ImportSource {
+    Monkeytype,
+    Typeracer,
+}
+
+impl From<ImportSource> for import::Source {
+    fn from(source: ImportSource) -> Self {
+        match source {
+            ImportSource::Monkeytype => import::Source::Monkeytype,
+            ImportSource::Typeracer => import::Source::Typeracer,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl From<ExportFormat> for export::Format {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Csv => export::Format::Csv,
+            ExportFormat::Json => export::Format::Json,
+            ExportFormat::Markdown => export::Format::Markdown,
+            ExportFormat::Html => export::Format::Html,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+enum MarkovUnit {
+    Char,
+    Word,
+}
+
+impl From<MarkovUnit> for Unit {
+    fn from(unit: MarkovUnit) -> Self {
+        match unit {
+            MarkovUnit::Char => Unit::Char,
+            MarkovUnit::Word => Unit::Word,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, ValueEnum, strum_macros::Display)]
@@ -66,90 +285,928 @@ enum SupportedLanguage {
 }
 
 impl SupportedLanguage {
+    /// As passed on the command line, which is also its file name.
+    fn name(&self) -> String {
+        self.to_string().to_lowercase()
+    }
+
     fn as_lang(&self) -> Language {
-        Language::new(self.to_string().to_lowercase())
+        Language::new(self.name()).expect("supported languages are bundled")
     }
 }
 
+/// A test's results and how its prompt was made, as printed by `--json` and
+/// `thokr simulate`.
+#[derive(Debug, Serialize)]
+struct Report {
+    #[serde(flatten)]
+    results: Results,
+    #[serde(flatten)]
+    settings: history::Settings,
+}
+
 #[derive(Debug)]
 struct App {
     cli: Cli,
     thok: Thok,
+    markov: Option<Markov>,
+    keyboard: KeyboardLayout,
+    /// layout to remap typed keys onto, see `Cli::emulate_layout`
+    emulate: Option<KeyboardLayout>,
+    /// curriculum progress when running `thokr learn`
+    learn: Option<learn::Progress>,
+    /// why the saved lesson progress couldn't be read; it's left alone
+    /// rather than overwritten then
+    learn_unreadable: Option<String>,
+    /// lesson the current prompt drills (0-based), recorded with its results
+    lesson: Option<usize>,
+    /// mistyped words scheduled for review
+    review: review::ReviewStore,
+    /// why the saved review queue couldn't be read; it's left alone rather
+    /// than overwritten then
+    review_unreadable: Option<String>,
+    /// how the current prompt was made, recorded in the history
+    settings: history::Settings,
+    /// where everything thokr records is read from and saved to
+    data_dir: Option<PathBuf>,
+    /// whether the results of the current test have been computed
+    finished: bool,
+    /// time source of every test, faked by `thokr simulate`
+    clock: Arc<dyn Clock>,
+    /// results of the last finished test, kept for `--json` and `--output`
+    report: Option<Report>,
+    /// what the results screen notes under the stats
+    notes: Notes,
+    /// connection to the host when racing
+    racer: Option<race::Racer>,
+    /// the race the current test is part of, see `thokr host`
+    race: Option<race::Race>,
+    /// the race being hosted, see `thokr host`
+    hosting: Option<thread::JoinHandle<io::Result<()>>>,
+    /// where tests are shown to `thokr watch`, see `Cli::publish`
+    publisher: Option<spectate::Publisher>,
+}
+
+/// Lines the results screen adds under the stats, about everything recorded
+/// besides the test itself.
+#[derive(Debug, Default)]
+struct Notes {
+    daily: Option<daily::DailySummary>,
+    lesson: Option<learn::LessonSummary>,
+    review: Option<review::ReviewSummary>,
+    /// where the results card was saved, see `card`
+    card: Option<PathBuf>,
+    /// what couldn't be saved after the test finished
+    save_errors: Vec<String>,
+}
+
+impl Cli {
+    /// A flag that picks the prompt, which `thokr learn` does itself. clap
+    /// can't make flags conflict with a subcommand, hence the check.
+    fn learn_conflict(&self) -> Option<&'static str> {
+        if !matches!(self.command, Some(Command::Learn { .. })) {
+            return None;
+        }
+        [
+            (self.prompt.is_some(), "--prompt"),
+            (self.number_of_sentences.is_some(), "--full-sentences"),
+            (self.daily, "--daily"),
+            (self.markov.is_some(), "--markov"),
+            (self.review, "--review"),
+        ]
+        .into_iter()
+        .find_map(|(used, flag)| used.then_some(flag))
+    }
 }
 
 impl App {
-    /// (prompt, word_count) per the CLI flags.
-    fn generate_prompt(cli: &Cli) -> (String, usize) {
-        if let Some(p) = &cli.prompt {
-            (p.clone(), cli.number_of_words)
+    /// Lesson the next prompt of `thokr learn` drills (0-based).
+    fn next_lesson(&self) -> Option<usize> {
+        let progress = self.learn.as_ref()?;
+        match &self.cli.command {
+            Some(Command::Learn {
+                lesson: Some(lesson),
+            }) => Some((lesson.max(&1) - 1).min(progress.current())),
+            _ => Some(progress.current()),
+        }
+    }
+
+    /// Why `--lesson` can't be practiced yet, if it can't.
+    fn locked_lesson(&self) -> Option<String> {
+        let progress = self.learn.as_ref()?;
+        match &self.cli.command {
+            Some(Command::Learn {
+                lesson: Some(lesson),
+            }) if *lesson == 0 || lesson - 1 > progress.current() => Some(format!(
+                "lesson {} is locked: lessons 1 to {} are unlocked",
+                lesson,
+                progress.current() + 1
+            )),
+            _ => None,
+        }
+    }
+
+    /// A fresh prompt, its word count and the settings that produced it.
+    fn generate_prompt(&self) -> (String, usize, history::Settings) {
+        let cli = &self.cli;
+        let settings = |mode, language: Option<String>, seed| history::Settings {
+            mode: Some(mode),
+            language,
+            seed,
+            pace: cli.pace.map(f64::from),
+            emulate_layout: cli.emulate_layout.clone(),
+            ..history::Settings::default()
+        };
+
+        if let Some(lesson) = self.lesson {
+            let words = learn::generate(
+                &mut rand::thread_rng(),
+                &self.keyboard,
+                lesson,
+                cli.number_of_words,
+            );
+            (
+                words.join(" "),
+                words.len(),
+                settings(history::Mode::Lesson, None, None),
+            )
+        } else if cli.review && !self.review.due(review::today()).is_empty() {
+            let words = self.review.prompt(
+                &mut rand::thread_rng(),
+                review::today(),
+                cli.number_of_words,
+            );
+            (
+                words.join(" "),
+                words.len(),
+                settings(history::Mode::Review, None, None),
+            )
+        } else if let Some(markov) = &self.markov {
+            let words = match cli.seed {
+                Some(seed) => {
+                    markov.generate(&mut StdRng::seed_from_u64(seed), cli.number_of_words)
+                }
+                None => markov.generate(&mut rand::thread_rng(), cli.number_of_words),
+            };
+            (
+                words.join(" "),
+                words.len(),
+                history::Settings {
+                    markov_unit: cli.markov.map(|unit| unit.to_string()),
+                    markov_order: Some(cli.order),
+                    corpus: cli.corpus.as_ref().map(|c| c.display().to_string()),
+                    ..settings(history::Mode::Markov, None, cli.seed)
+                },
+            )
+        } else if let Some(p) = &cli.prompt {
+            (
+                p.clone(),
+                p.split_whitespace().count(),
+                settings(history::Mode::Prompt, None, None),
+            )
         } else if let Some(n) = cli.number_of_sentences {
             let language = cli.supported_language.as_lang();
             let (s, count) = language.get_random_sentence(n);
-            (s.join(""), count)
+            (
+                s.join(""),
+                count,
+                settings(
+                    history::Mode::Sentences,
+                    Some(cli.supported_language.name()),
+                    None,
+                ),
+            )
+        } else if cli.daily {
+            let language = Language::new(daily::DAILY_LANGUAGE.to_string())
+                .expect("the daily language is bundled");
+            let seed = daily::seed_for(daily::today());
+            let mut rng = StdRng::seed_from_u64(seed);
+            let words =
+                language.get_random_with(&mut rng, daily::DAILY_WORDS, &Sampling::default());
+            (
+                words.join(" "),
+                words.len(),
+                settings(
+                    history::Mode::Daily,
+                    Some(daily::DAILY_LANGUAGE.to_string()),
+                    Some(seed),
+                ),
+            )
         } else {
             let language = cli.supported_language.as_lang();
+            let sampling = Sampling {
+                weighted: cli.weighted,
+                min_gap: cli.repeat_gap,
+            };
+            let words = match cli.seed {
+                Some(seed) => language.get_random_with(
+                    &mut StdRng::seed_from_u64(seed),
+                    cli.number_of_words,
+                    &sampling,
+                ),
+                None => language.get_random(cli.number_of_words, &sampling),
+            };
             (
-                language.get_random(cli.number_of_words).join(" "),
-                cli.number_of_words,
+                words.join(" "),
+                words.len(),
+                history::Settings {
+                    weighted: Some(sampling.weighted),
+                    repeat_gap: Some(sampling.min_gap),
+                    ..settings(
+                        history::Mode::Words,
+                        Some(cli.supported_language.name()),
+                        cli.seed,
+                    )
+                },
             )
         }
     }
 
-    fn new(cli: Cli) -> Self {
-        let (prompt, count) = Self::generate_prompt(&cli);
-        let mut thok = Thok::new(prompt, count, cli.number_of_secs.map(|ns| ns as f64));
-        thok.pace_wpm = cli.pace.map(f64::from);
-        Self { thok, cli }
+    fn new(
+        cli: Cli,
+        markov: Option<Markov>,
+        keyboard: KeyboardLayout,
+        emulate: Option<KeyboardLayout>,
+        data_dir: Option<PathBuf>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let mut learn_unreadable = None;
+        let learn = matches!(cli.command, Some(Command::Learn { .. })).then(|| {
+            match data_dir.as_deref().map(learn::Progress::load) {
+                Some(Ok(progress)) => progress,
+                Some(Err(e)) => {
+                    learn_unreadable = Some(e.to_string());
+                    learn::Progress::default()
+                }
+                None => learn::Progress::default(),
+            }
+        });
+        let mut review_unreadable = None;
+        let review = match data_dir.as_deref().map(review::ReviewStore::load) {
+            Some(Ok(review)) => review,
+            Some(Err(e)) => {
+                review_unreadable = Some(e.to_string());
+                review::ReviewStore::default()
+            }
+            None => review::ReviewStore::default(),
+        };
+        let mut app = Self {
+            thok: Thok::new(String::new(), 0, None),
+            cli,
+            markov,
+            keyboard,
+            emulate,
+            learn,
+            learn_unreadable,
+            lesson: None,
+            review,
+            review_unreadable,
+            settings: history::Settings::default(),
+            data_dir,
+            finished: false,
+            clock,
+            report: None,
+            notes: Notes::default(),
+            racer: None,
+            race: None,
+            hosting: None,
+            publisher: None,
+        };
+        app.reset(None);
+        app
+    }
+
+    /// The char `c` would produce on the emulated layout, if any.
+    fn emulate_key(&self, c: char) -> char {
+        match &self.emulate {
+            Some(layout) => layout.emulate(&KeyboardLayout::default(), c),
+            None => c,
+        }
+    }
+
+    /// Wraps up the current test once it's complete or out of time; true if
+    /// it just finished. An empty prompt is complete without ever starting,
+    /// and has no results to record.
+    fn finish_if_done(&mut self) -> bool {
+        if self.finished || !self.thok.has_finished() || !self.thok.has_started() {
+            return false;
+        }
+        self.finished = true;
+        self.on_finish();
+        true
+    }
+
+    /// Computes and persists the results of a test that just finished.
+    /// Anything that fails to save is reported on the results screen.
+    fn on_finish(&mut self) {
+        self.thok.calc_results();
+        let mut errors = vec![];
+        let save_dir = self.data_dir.clone().filter(|_| !self.cli.no_history);
+
+        if let Some(dir) = &save_dir {
+            match history::append(dir, &history::Entry::of(&self.thok, &self.settings)) {
+                Ok(0) => {}
+                Ok(recovered) => errors.push(format!(
+                    "moved {} unreadable history line{} to history.corrupt",
+                    recovered,
+                    if recovered == 1 { "" } else { "s" }
+                )),
+                Err(e) => errors.push(format!("unable to save results to history: {}", e)),
+            }
+        }
+
+        // a daily only counts if the whole prompt was typed, so `-s 5` can't
+        // be used to post a sprint as the day's result
+        // lesson drills are never the day's challenge
+        let daily = self.cli.daily && self.lesson.is_none();
+        if daily && !self.thok.completed_prompt() {
+            errors.push(String::from(
+                "daily challenge not recorded: time ran out before the prompt was done",
+            ));
+        } else if daily {
+            let record = daily::DailyRecord {
+                date: daily::today(),
+                wpm: self.thok.wpm(),
+                accuracy: self.thok.accuracy(),
+            };
+            if let Some(dir) = &save_dir {
+                if let Err(e) = daily::save(dir, &record) {
+                    errors.push(format!("unable to save daily challenge: {}", e));
+                }
+            }
+            let mut records = self
+                .data_dir
+                .as_deref()
+                .map(daily::load)
+                .unwrap_or_default();
+            if save_dir.is_none() {
+                records.push(record);
+            }
+            self.notes.daily = daily::summary(&records, record.date);
+        }
+
+        if let (Some(lesson), Some(progress)) = (self.lesson, self.learn.as_mut()) {
+            self.notes.lesson = Some(progress.record(
+                lesson,
+                learn::LessonResult {
+                    wpm: self.thok.wpm(),
+                    accuracy: self.thok.accuracy(),
+                },
+            ));
+            if let Some(dir) = save_dir.as_deref() {
+                if let Some(e) = &self.learn_unreadable {
+                    errors.push(format!(
+                        "lesson progress not saved: unable to read learn.json: {}",
+                        e
+                    ));
+                } else if let Err(e) = progress.save(dir) {
+                    errors.push(format!("unable to save lesson progress: {}", e));
+                }
+            }
+        } else {
+            // lesson drills aren't real words, so only other tests feed reviews
+            let summary = self
+                .review
+                .record(&self.thok.word_outcomes(), review::today());
+            if let Some(dir) = save_dir.as_deref() {
+                if let Some(e) = &self.review_unreadable {
+                    errors.push(format!(
+                        "review queue not saved: unable to read review.json: {}",
+                        e
+                    ));
+                } else if let Err(e) = self.review.save(dir) {
+                    errors.push(format!("unable to save review queue: {}", e));
+                }
+            }
+            if summary.mistakes > 0 || self.cli.review {
+                self.notes.review = Some(summary);
+            }
+        }
+
+        self.notes.save_errors = errors;
+        if self.cli.json || self.cli.output.is_some() {
+            self.report = Some(self.report());
+        }
+    }
+
+    /// Saves the results of the finished test as an image to share, see
+    /// `Cli::card_dir`.
+    fn export_card(&mut self) {
+        let dir = self
+            .cli
+            .card_dir
+            .clone()
+            .or_else(paths::default_card_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
+        match card::save(&dir, &self.thok, &self.settings) {
+            Ok(path) => self.notes.card = Some(path),
+            Err(e) => self
+                .notes
+                .save_errors
+                .push(format!("unable to export card: {}", e)),
+        }
+    }
+
+    fn report(&self) -> Report {
+        Report {
+            results: self.thok.results(),
+            settings: self.settings.clone(),
+        }
     }
 
     fn reset(&mut self, new_prompt: Option<String>) {
         let (prompt, count) = match new_prompt {
-            Some(p) => (p, self.thok.number_of_words),
-            None => Self::generate_prompt(&self.cli),
+            Some(p) => (p, self.thok.number_of_words()),
+            // a retry drills the same lesson, whatever it unlocked
+            None => {
+                self.lesson = self.next_lesson();
+                let (prompt, count, settings) = self.generate_prompt();
+                self.settings = settings;
+                (prompt, count)
+            }
         };
-        self.thok = Thok::new(prompt, count, self.cli.number_of_secs.map(|ns| ns as f64));
-        self.thok.pace_wpm = self.cli.pace.map(f64::from);
+        self.load(prompt, count, self.cli.number_of_secs.map(|ns| ns as f64));
+    }
+
+    /// Starts a fresh test of `prompt`.
+    fn load(&mut self, prompt: String, number_of_words: usize, number_of_secs: Option<f64>) {
+        self.thok = Thok::with_clock(prompt, number_of_words, number_of_secs, self.clock.clone());
+        self.finished = false;
+        self.race = None;
+        self.notes = Notes::default();
+        self.thok.set_pace_wpm(self.cli.pace.map(f64::from));
+        self.thok.set_keyboard(self.keyboard.clone());
+    }
+
+    /// Races the prompt of `setup` as racer `id`.
+    fn start_race(&mut self, racer: race::Racer, id: usize, setup: race::Setup) {
+        self.settings = setup.settings;
+        self.lesson = None;
+        self.load(setup.prompt, setup.number_of_words, setup.number_of_secs);
+        self.race = Some(race::Race {
+            me: id,
+            racers: setup.racers,
+            ..race::Race::default()
+        });
+        self.racer = Some(racer);
+    }
+
+    /// Whether typing has to wait for the race to start.
+    fn waiting_for_start(&self) -> bool {
+        self.race.as_ref().is_some_and(|race| !race.started())
+    }
+
+    /// Lets other racers and watchers know where the current test is at.
+    fn share_progress(&mut self) {
+        self.check_hosting();
+        self.sync_race();
+        if let Some(publisher) = &mut self.publisher {
+            publisher.publish(&spectate::Snapshot::of(&self.thok));
+        }
+    }
+
+    /// Tells the host how far along this racer is and takes in everyone
+    /// else's progress.
+    fn sync_race(&mut self) {
+        let cursor = self.thok.cursor_pos();
+        let finish = self.finished.then(|| race::Finish {
+            wpm: self.thok.wpm(),
+            accuracy: self.thok.accuracy(),
+            elapsed_secs: self.thok.elapsed_secs().unwrap_or(0.0),
+        });
+        let (Some(racer), Some(race)) = (self.racer.as_mut(), self.race.as_mut()) else {
+            return;
+        };
+
+        let sent = racer
+            .progress(cursor)
+            .and_then(|_| finish.map_or(Ok(()), |finish| racer.finish(finish)));
+        racer.update(race);
+        race.disconnected |= sent.is_err();
+    }
+
+    /// Shows why the race this app hosts ended early, if it did.
+    fn check_hosting(&mut self) {
+        if !self.hosting.as_ref().is_some_and(|h| h.is_finished()) {
+            return;
+        }
+        let error = match self.hosting.take().unwrap().join() {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => String::from("the host crashed"),
+        };
+        if let Some(race) = self.race.as_mut() {
+            race.host_error = Some(error);
+        }
+    }
+}
+
+/// `thokr export`: the history recorded in `data_dir`, written to `output`
+/// or stdout.
+fn export_history(
+    data_dir: Option<&Path>,
+    format: ExportFormat,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    output: Option<&Path>,
+) -> io::Result<()> {
+    let entries = match data_dir {
+        Some(dir) => history::load(dir)?,
+        None => vec![],
+    };
+    let exported = export::render(&export::filter(entries, since, until), format.into());
+    match output {
+        Some(path) => fs::write(path, exported),
+        None => io::stdout().write_all(exported.as_bytes()),
+    }
+}
+
+/// A built-in or custom keyboard layout; exits with a usage error otherwise.
+fn load_keyboard_layout(name_or_path: &str) -> KeyboardLayout {
+    KeyboardLayout::load(name_or_path).unwrap_or_else(|e| {
+        let mut cmd = Cli::command();
+        cmd.error(
+            ErrorKind::InvalidValue,
+            format!(
+                "unable to load keyboard layout {}: {} (built-in layouts: {})",
+                name_or_path,
+                e,
+                keyboard::BUILTIN_LAYOUTS.join(", ")
+            ),
+        )
+        .exit()
+    })
+}
+
+/// `addr`, on `port` unless it names one. IPv6 addresses need brackets to
+/// name a port, as in URLs: `[::1]:7878`.
+fn with_port(addr: &str, port: u16) -> String {
+    if addr.parse::<SocketAddr>().is_ok() {
+        return addr.to_string();
+    }
+    let unbracketed = addr.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+        return SocketAddr::new(ip, port).to_string();
+    }
+    match addr.rsplit_once(':') {
+        Some((_, named)) if named.parse::<u16>().is_ok() => addr.to_string(),
+        _ => format!("{}:{}", addr, port),
     }
 }
 
+/// Where the TUI draws: stdout, unless that's piped (`thokr --json | jq`),
+/// then the terminal itself, so only the results go down the pipe.
+fn screen() -> Box<dyn Write> {
+    if io::stdout().is_tty() {
+        return Box::new(io::stdout());
+    }
+    match fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(io::stderr()),
+    }
+}
+
+/// Switches the terminal to raw mode and the alternate screen.
+fn enter_terminal() -> io::Result<Terminal<CrosstermBackend<Box<dyn Write>>>> {
+    enable_raw_mode()?;
+
+    let mut screen = screen();
+    execute!(screen, EnterAlternateScreen, SetCursorStyle::SteadyBar)?;
+    let backend = CrosstermBackend::new(screen);
+    Terminal::new(backend)
+}
+
 /// Best-effort terminal restore; used on panic and on exit.
 fn restore_terminal() {
     let _ = disable_raw_mode();
     let _ = execute!(
-        io::stdout(),
+        screen(),
         SetCursorStyle::DefaultUserShape,
         LeaveAlternateScreen
     );
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
-    if !stdin().is_tty() {
+    if let Some(flag) = cli.learn_conflict() {
         let mut cmd = Cli::command();
-        cmd.error(ErrorKind::Io, "stdin must be a tty").exit();
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            format!("the argument '{}' cannot be used with 'learn'", flag),
+        )
+        .exit();
+    }
+
+    if cli.data_dir.is_none() && !cli.no_history {
+        if let Err(e) = paths::migrate_config_dir() {
+            let mut cmd = Cli::command();
+            cmd.error(
+                ErrorKind::Io,
+                format!(
+                    "unable to move thokr's history from its config dir to its data dir: {}",
+                    e
+                ),
+            )
+            .exit();
+        }
+    }
+    let data_dir = cli.data_dir.clone().or_else(paths::default_data_dir);
+
+    if let Some(Command::Export {
+        format,
+        since,
+        until,
+        output,
+    }) = &cli.command
+    {
+        if let Err(e) = export_history(
+            data_dir.as_deref(),
+            *format,
+            *since,
+            *until,
+            output.as_deref(),
+        ) {
+            let mut cmd = Cli::command();
+            cmd.error(ErrorKind::Io, format!("unable to export history: {}", e))
+                .exit();
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Import { from, file }) = &cli.command {
+        let source = import::Source::from(*from);
+        let mut cmd = Cli::command();
+        let dir = match (&data_dir, cli.no_history) {
+            (Some(dir), false) => dir,
+            _ => cmd
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "there is no history to import into with --no-history",
+                )
+                .exit(),
+        };
+        let imported = fs::read_to_string(file)
+            .and_then(|contents| import::parse(source, &contents))
+            .unwrap_or_else(|e| {
+                cmd.error(
+                    ErrorKind::Io,
+                    format!("unable to read {}: {}", file.display(), e),
+                )
+                .exit()
+            });
+        let total = imported.entries.len();
+        let added = history::merge(dir, imported.entries).unwrap_or_else(|e| {
+            cmd.error(
+                ErrorKind::Io,
+                format!("unable to save imported results: {}", e),
+            )
+            .exit()
+        });
+        println!(
+            "imported {} of {} {} results ({} already in the history, {} unreadable rows skipped)",
+            added,
+            total,
+            source.name(),
+            total - added,
+            imported.skipped
+        );
+        return Ok(());
+    }
+
+    if cli.weighted && !cli.supported_language.as_lang().frequency_ordered() {
+        let mut cmd = Cli::command();
+        cmd.error(
+            ErrorKind::InvalidValue,
+            format!(
+                "--weighted needs a word list ordered by frequency, which {} isn't",
+                cli.supported_language.name()
+            ),
+        )
+        .exit();
     }
 
+    let markov = cli.markov.map(|unit| {
+        let corpus = match &cli.corpus {
+            Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+                let mut cmd = Cli::command();
+                cmd.error(ErrorKind::Io, format!("unable to read corpus: {}", e))
+                    .exit()
+            }),
+            None => lang::builtin_corpus().to_string(),
+        };
+        Markov::train(&corpus, unit.into(), cli.order).unwrap_or_else(|| {
+            let mut cmd = Cli::command();
+            cmd.error(
+                ErrorKind::InvalidValue,
+                "corpus is too short for this order",
+            )
+            .exit()
+        })
+    });
+
     let default_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
         restore_terminal();
         default_hook(info);
     }));
 
-    enable_raw_mode()?;
+    if cli.review {
+        let review = data_dir
+            .as_deref()
+            .map(review::ReviewStore::load)
+            .transpose()
+            .unwrap_or_else(|e| {
+                let mut cmd = Cli::command();
+                cmd.error(
+                    ErrorKind::Io,
+                    format!("unable to read the review queue: {}", e),
+                )
+                .exit()
+            })
+            .unwrap_or_default();
+        if review.due(review::today()).is_empty() {
+            let mut cmd = Cli::command();
+            cmd.error(ErrorKind::InvalidValue, "no words are due for review")
+                .exit();
+        }
+    }
+
+    let emulate = cli.emulate_layout.as_deref().map(load_keyboard_layout);
+    let keyboard = load_keyboard_layout(
+        cli.keyboard
+            .as_deref()
+            .or(cli.emulate_layout.as_deref())
+            .unwrap_or("qwerty"),
+    );
+
+    if let Some(Command::Simulate { timeline }) = &cli.command {
+        let timeline = fs::read_to_string(timeline)
+            .and_then(|contents| simulate::parse_timeline(&contents))
+            .unwrap_or_else(|e| {
+                let mut cmd = Cli::command();
+                cmd.error(
+                    ErrorKind::Io,
+                    format!("unable to read {}: {}", timeline.display(), e),
+                )
+                .exit()
+            });
+        let clock = FakeClock::new();
+        let mut app = App::new(
+            cli.clone(),
+            markov,
+            keyboard,
+            emulate,
+            data_dir,
+            Arc::new(clock.clone()),
+        );
+        let timeline = timeline
+            .into_iter()
+            .map(|keystroke| simulate::Keystroke {
+                key: match keystroke.key {
+                    simulate::Key::Char(c) => simulate::Key::Char(app.emulate_key(c)),
+                    key => key,
+                },
+                ..keystroke
+            })
+            .collect::<Vec<_>>();
+        simulate::run(&mut app.thok, &clock, &timeline);
+        println!("{}", serde_json::to_string_pretty(&app.report())?);
+        return Ok(());
+    }
+
+    if !stdin().is_tty() {
+        let mut cmd = Cli::command();
+        cmd.error(ErrorKind::Io, "stdin must be a tty").exit();
+    }
+
+    if let Some(Command::Watch { addr }) = &cli.command {
+        let addr = with_port(addr, spectate::DEFAULT_PORT);
+        let watcher = spectate::Watcher::connect(&addr).unwrap_or_else(|e| {
+            let mut cmd = Cli::command();
+            cmd.error(ErrorKind::Io, format!("unable to watch {}: {}", addr, e))
+                .exit()
+        });
+
+        let mut terminal = enter_terminal()?;
+        let res = watch_tui(
+            &mut terminal,
+            &watcher,
+            &addr,
+            watcher.events(event::Crossterm),
+        );
+        restore_terminal();
+        terminal.show_cursor()?;
+        return res;
+    }
+
+    if matches!(cli.command, Some(Command::Host { .. })) {
+        // everyone gets the same words from the same seed
+        cli.seed.get_or_insert_with(rand::random);
+    }
+
+    let mut app = App::new(
+        cli,
+        markov,
+        keyboard,
+        emulate,
+        data_dir,
+        Arc::new(SystemClock),
+    );
+    if let Some(locked) = app.locked_lesson() {
+        let mut cmd = Cli::command();
+        cmd.error(ErrorKind::InvalidValue, locked).exit();
+    }
+
+    let joined = match &app.cli.command {
+        Some(Command::Host { port, racers, name }) => {
+            let listener = TcpListener::bind(("0.0.0.0", *port)).unwrap_or_else(|e| {
+                let mut cmd = Cli::command();
+                cmd.error(
+                    ErrorKind::Io,
+                    format!("unable to host a race on port {}: {}", port, e),
+                )
+                .exit()
+            });
+            let setup = race::Setup {
+                prompt: app.thok.prompt().to_string(),
+                number_of_words: app.thok.number_of_words(),
+                number_of_secs: app.thok.number_of_secs(),
+                settings: app.settings.clone(),
+                racers: (*racers).into(),
+                countdown_secs: race::COUNTDOWN_SECS,
+            };
+            app.hosting = Some(thread::spawn(move || race::host(listener, setup)));
+            Some((format!("127.0.0.1:{}", port), name.clone()))
+        }
+        Some(Command::Join { addr, name }) => {
+            Some((with_port(addr, race::DEFAULT_PORT), name.clone()))
+        }
+        _ => None,
+    };
+    if let Some((addr, name)) = joined {
+        let name = name
+            .or_else(|| env::var("USER").ok())
+            .unwrap_or_else(|| String::from("racer"));
+        let (racer, id, setup) = race::Racer::join(&addr, &name).unwrap_or_else(|e| {
+            let mut cmd = Cli::command();
+            cmd.error(
+                ErrorKind::Io,
+                format!("unable to join the race at {}: {}", addr, e),
+            )
+            .exit()
+        });
+        app.start_race(racer, id, setup);
+    }
+
+    if let Some(addr) = &app.cli.publish {
+        let listener = TcpListener::bind(addr).unwrap_or_else(|e| {
+            let mut cmd = Cli::command();
+            cmd.error(
+                ErrorKind::Io,
+                format!("unable to publish on {}: {}", addr, e),
+            )
+            .exit()
+        });
+        app.publisher = Some(spectate::Publisher::new(listener));
+    }
 
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, SetCursorStyle::SteadyBar)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = enter_terminal()?;
 
-    let mut app = App::new(cli);
-    let res = start_tui(&mut terminal, &mut app);
+    let res = match app.racer.as_ref().map(|r| r.events(event::Crossterm)) {
+        Some(events) => start_tui(&mut terminal, &mut app, events),
+        None => start_tui(&mut terminal, &mut app, event::Crossterm),
+    };
 
     restore_terminal();
     terminal.show_cursor()?;
+    res?;
+
+    if app.cli.json {
+        print_report(&app, &mut io::stdout())?;
+    }
+    if let (Some(report), Some(path)) = (&app.report, &app.cli.output) {
+        let json = serde_json::to_string_pretty(report)?;
+        if let Err(e) = fs::write(path, json + "\n") {
+            let mut cmd = Cli::command();
+            cmd.error(
+                ErrorKind::Io,
+                format!("unable to write results to {}: {}", path.display(), e),
+            )
+            .exit();
+        }
+    }
 
-    res
+    Ok(())
+}
+
+/// `--json`: the report of the last finished test, and nothing else, so
+/// stdout can be piped.
+fn print_report(app: &App, out: &mut impl Write) -> io::Result<()> {
+    match &app.report {
+        Some(report) => writeln!(out, "{}", serde_json::to_string_pretty(report)?),
+        None => Ok(()),
+    }
 }
 
 enum ExitType {
@@ -157,47 +1214,48 @@ enum ExitType {
     New,
     Quit,
 }
+/// Runs tests until the user quits, taking keys from `events`.
 fn start_tui<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: &mut App,
+    mut events: impl EventSource,
 ) -> Result<(), Box<dyn Error>>
 where
     <B as Backend>::Error: 'static,
 {
-    let should_tick = app.cli.number_of_secs.unwrap_or(0) > 0 || app.cli.pace.is_some();
-
-    let thok_events = get_thok_events(should_tick);
-
     loop {
         let mut exit_type: ExitType = ExitType::Quit;
+        app.share_progress();
         terminal.draw(|f| ui(app, f))?;
 
         loop {
             let app = &mut app;
 
-            match thok_events.recv()? {
-                ThokEvent::Tick => {
-                    if app.thok.has_started() && !app.thok.has_finished() {
-                        app.thok.on_tick();
+            // wait for a key, or only until the countdown or pace caret moves
+            let event = events.next_event(app.thok.next_tick())?;
 
-                        if app.thok.has_finished() {
-                            app.thok.calc_results();
-                            let _ = app.thok.save_results();
-                        }
-                        terminal.draw(|f| ui(app, f))?;
-                    }
-                }
-                ThokEvent::Resize => {
+            // a timed test ends at its deadline, before any key that comes later
+            if app.finish_if_done() {
+                terminal.draw(|f| ui(app, f))?;
+            }
+            app.share_progress();
+
+            match event {
+                None | Some(Event::Resize(_, _)) => {
                     terminal.draw(|f| ui(app, f))?;
                 }
-                ThokEvent::Key(key) => {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
                     match key.code {
                         KeyCode::Esc => {
                             break;
                         }
-                        KeyCode::Backspace if !app.thok.has_finished() => {
+                        KeyCode::Backspace
+                            if !app.thok.has_finished() && !app.waiting_for_start() =>
+                        {
                             app.thok.backspace();
                         }
+                        // a race is a single test
+                        KeyCode::Left | KeyCode::Right if app.racer.is_some() => {}
                         KeyCode::Left => {
                             exit_type = ExitType::Restart;
                             break;
@@ -215,18 +1273,19 @@ where
                             }
 
                             match app.thok.has_finished() {
+                                false if app.waiting_for_start() => {}
                                 false => {
+                                    let c = app.emulate_key(c);
                                     app.thok.write(c);
-                                    if app.thok.has_finished() {
-                                        app.thok.calc_results();
-                                        let _ = app.thok.save_results();
-                                    }
+                                    app.finish_if_done();
                                 }
                                 true => match key.code {
                                     KeyCode::Char('t') if Browser::is_available() => {
-                                        webbrowser::open(&format!("https://twitter.com/intent/tweet?text={}%20wpm%20%2F%20{}%25%20acc%20%2F%20{:.2}%20sd%0A%0Ahttps%3A%2F%2Fgithub.com%2Fthatvegandev%2Fthokr", app.thok.wpm, app.thok.accuracy, app.thok.std_dev))
+                                        webbrowser::open(&format!("https://twitter.com/intent/tweet?text={}%20wpm%20%2F%20{}%25%20acc%20%2F%20{:.2}%20sd%0A%0Ahttps%3A%2F%2Fgithub.com%2Fthatvegandev%2Fthokr", app.thok.wpm(), app.thok.accuracy(), app.thok.std_dev()))
                                     .unwrap_or_default();
                                     }
+                                    KeyCode::Char('e') => app.export_card(),
+                                    KeyCode::Char('r' | 'n') if app.racer.is_some() => {}
                                     KeyCode::Char('r') => {
                                         exit_type = ExitType::Restart;
                                         break;
@@ -235,20 +1294,31 @@ where
                                         exit_type = ExitType::New;
                                         break;
                                     }
+                                    KeyCode::Char('k') => {
+                                        app.thok.toggle_results_view(ResultsView::Keyboard);
+                                    }
+                                    KeyCode::Char('w') => {
+                                        app.thok.toggle_results_view(ResultsView::Words);
+                                    }
+                                    KeyCode::Char('d') => {
+                                        app.thok.toggle_results_view(ResultsView::Diff);
+                                    }
                                     _ => {}
                                 },
                             }
                         }
                         _ => {}
                     }
+                    app.share_progress();
                     terminal.draw(|f| ui(app, f))?;
                 }
+                Some(_) => {}
             }
         }
 
         match exit_type {
             ExitType::Restart => {
-                app.reset(Some(app.thok.prompt.clone()));
+                app.reset(Some(app.thok.prompt().to_string()));
             }
             ExitType::New => {
                 app.reset(None);
@@ -262,46 +1332,819 @@ where
     Ok(())
 }
 
-#[derive(Clone)]
-enum ThokEvent {
-    Key(KeyEvent),
-    Resize,
-    Tick,
+/// `thokr watch`: draws the test published at `addr` as it's typed, until
+/// the user quits.
+fn watch_tui<B: Backend>(
+    terminal: &mut Terminal<B>,
+    watcher: &spectate::Watcher,
+    addr: &str,
+    mut events: impl EventSource,
+) -> Result<(), Box<dyn Error>>
+where
+    <B as Backend>::Error: 'static,
+{
+    let mut thok: Option<Thok> = None;
+
+    loop {
+        if let Some(snapshot) = watcher.take() {
+            thok = Some(snapshot.to_thok());
+        }
+        let status = if watcher.hung_up() {
+            format!("{} stopped publishing", addr)
+        } else if thok.is_none() {
+            format!("waiting for a test at {}", addr)
+        } else {
+            String::new()
+        };
+
+        terminal.draw(|f| {
+            let area = f.area();
+            if let Some(thok) = &thok {
+                f.render_widget(thok, area);
+                if let Some(pos) = ui::cursor_screen_position(thok, area) {
+                    f.set_cursor_position(pos);
+                }
+            }
+            f.render_widget(
+                Paragraph::new(Span::styled(
+                    status,
+                    Style::default().add_modifier(Modifier::DIM),
+                ))
+                .alignment(Alignment::Center),
+                Rect::new(
+                    area.x,
+                    area.bottom().saturating_sub(1),
+                    area.width,
+                    1.min(area.height),
+                ),
+            );
+        })?;
+
+        match events.next_event(thok.as_ref().and_then(Thok::next_tick))? {
+            Some(Event::Key(key))
+                if key.kind == KeyEventKind::Press
+                    && (key.code == KeyCode::Esc
+                        || (key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('c'))) =>
+            {
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
 }
 
-fn get_thok_events(should_tick: bool) -> mpsc::Receiver<ThokEvent> {
-    let (tx, rx) = mpsc::channel();
+fn ui(app: &mut App, f: &mut Frame) {
+    let area = f.area();
+    if app.thok.has_finished() {
+        render_results(app, area, f.buffer_mut());
+        return;
+    }
 
-    if should_tick {
-        let tick_x = tx.clone();
-        thread::spawn(move || loop {
-            if tick_x.send(ThokEvent::Tick).is_err() {
-                break;
+    f.render_widget(&app.thok, area);
+    if let Some(race) = &app.race {
+        // where opponents still typing are
+        let underlined = Style::default().add_modifier(Modifier::UNDERLINED);
+        for opponent in race.opponents().filter(|o| o.finish.is_none() && !o.left) {
+            if let Some(pos) = ui::char_screen_position(&app.thok, area, opponent.cursor) {
+                f.buffer_mut()
+                    .set_style(Rect::new(pos.x, pos.y, 1, 1), underlined);
             }
+        }
+        if let Some(status) = race_status(race) {
+            let timer = ui::timer_area(&app.thok, area);
+            f.render_widget(Clear, timer);
+            f.render_widget(
+                Paragraph::new(Span::styled(
+                    status,
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::DIM),
+                ))
+                .alignment(Alignment::Center),
+                timer,
+            );
+        }
+    }
+    if let Some(pos) = ui::cursor_screen_position(&app.thok, area) {
+        f.set_cursor_position(pos);
+    }
+}
 
-            thread::sleep(Duration::from_millis(TICK_RATE_MS))
-        });
+/// What a race shows in place of the timer until it's under way.
+fn race_status(race: &race::Race) -> Option<String> {
+    if let Some(e) = &race.host_error {
+        return Some(format!("unable to host the race: {}", e));
     }
+    if race.disconnected {
+        return Some(String::from("lost the connection to the host"));
+    }
+    match race.countdown {
+        None => Some(format!(
+            "waiting for racers {}/{}",
+            race.standings.len(),
+            race.racers
+        )),
+        Some(0) => None,
+        Some(secs) => Some(secs.to_string()),
+    }
+}
 
-    thread::spawn(move || loop {
-        let evt = match event::read().unwrap() {
-            Event::Key(key) if key.kind == KeyEventKind::Press => Some(ThokEvent::Key(key)),
-            Event::Key(_) => None,
-            Event::Resize(_, _) => Some(ThokEvent::Resize),
-            _ => None,
+/// The results, with the notes and the keys to press underneath.
+fn render_results(app: &App, area: Rect, buf: &mut Buffer) {
+    let width = area.width.saturating_sub(2 * ui::HORIZONTAL_MARGIN) as usize;
+    let notes = result_notes(app);
+
+    let mut keys = vec!["(k)eyboard", "(w)ords", "(d)iff", "(e)xport"];
+    if app.race.is_none() {
+        // races are one test only
+        keys.splice(0..0, ["(r)etry", "(n)ew"]);
+    }
+    if Browser::is_available() {
+        keys.push("(t)weet");
+    }
+    keys.push("(esc)ape");
+    let legend = ui::wrap_parts(&keys, " / ", width);
+
+    // the notes go right under the stats, in the results' bottom margin; on
+    // a small terminal the results keep a line of chart and one of stats
+    let footer_height = notes.len() as u16 + 1 + legend.len() as u16;
+    let results = Rect {
+        height: area
+            .height
+            .saturating_sub(footer_height)
+            .max(2 * ui::VERTICAL_MARGIN + 2)
+            .min(area.height),
+        ..area
+    };
+    (&app.thok).render(results, buf);
+
+    let footer = Rect {
+        y: results.bottom().saturating_sub(ui::VERTICAL_MARGIN),
+        height: footer_height,
+        ..area
+    }
+    .intersection(area);
+    let [notes_area, _, legend_area] = Layout::vertical([
+        Constraint::Length(notes.len() as u16),
+        Constraint::Length(1), // for padding
+        Constraint::Length(legend.len() as u16),
+    ])
+    .horizontal_margin(ui::HORIZONTAL_MARGIN)
+    .areas(footer);
+
+    Paragraph::new(notes)
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::DIM))
+        .alignment(Alignment::Center)
+        .render(notes_area, buf);
+    Paragraph::new(legend.into_iter().map(Line::from).collect::<Vec<_>>())
+        .style(Style::default().add_modifier(Modifier::ITALIC))
+        .render(legend_area, buf);
+}
+
+/// Lines about the daily challenge, lesson, review queue and race the test
+/// was part of, and anything that couldn't be saved.
+fn result_notes(app: &App) -> Vec<Line<'static>> {
+    let green_bold_style = Style::default()
+        .add_modifier(Modifier::BOLD)
+        .fg(Color::Green);
+    let red_bold_style = Style::default().add_modifier(Modifier::BOLD).fg(Color::Red);
+    let mut notes = vec![];
+
+    if let Some(daily) = &app.notes.daily {
+        notes.push(Line::from(format!(
+            "daily {}   best {} wpm   {}% acc   {} day streak",
+            daily.date.format("%Y-%m-%d"),
+            daily.best_wpm,
+            daily.best_accuracy,
+            daily.streak
+        )));
+    }
+
+    if let Some(summary) = &app.notes.lesson {
+        let lesson = &learn::LESSONS[summary.lesson];
+        let outcome = match (summary.passed, summary.unlocked) {
+            (_, Some(next)) => format!("unlocked: {}", learn::LESSONS[next].name),
+            (true, None) => String::from("passed"),
+            (false, None) => String::from("keep practicing"),
         };
+        notes.push(Line::from(format!(
+            "lesson {}/{} {}   target {} wpm   {}% acc   {}",
+            summary.lesson + 1,
+            learn::LESSONS.len(),
+            lesson.name,
+            lesson.target_wpm,
+            lesson.target_accuracy,
+            outcome
+        )));
+    }
 
-        if evt.is_some() && tx.send(evt.unwrap()).is_err() {
-            break;
+    if let Some(review) = &app.notes.review {
+        notes.push(Line::from(format!(
+            "{} mistyped word{} added to review   {} due (--review)",
+            review.mistakes,
+            if review.mistakes == 1 { "" } else { "s" },
+            review.due
+        )));
+    }
+
+    if let Some(race) = &app.race {
+        let mut place = 0;
+        for standing in race.ranking() {
+            let line = match &standing.finish {
+                Some(finish) => {
+                    place += 1;
+                    format!(
+                        "{}. {}   {} wpm   {}% acc",
+                        place, standing.name, finish.wpm, finish.accuracy
+                    )
+                }
+                None if standing.left => format!("{}   left", standing.name),
+                None => format!(
+                    "{}   typing {}%",
+                    standing.name,
+                    standing.cursor * 100 / app.thok.char_count().max(1)
+                ),
+            };
+            notes.push(if standing.id == race.me {
+                Line::styled(line, green_bold_style)
+            } else {
+                Line::from(line)
+            });
         }
-    });
+        if let Some(e) = &race.host_error {
+            notes.push(Line::styled(
+                format!("unable to host the race: {}", e),
+                red_bold_style,
+            ));
+        } else if race.disconnected && !race.over() {
+            notes.push(Line::styled(
+                "lost the connection to the host",
+                red_bold_style,
+            ));
+        }
+    }
 
-    rx
+    if let Some(card) = &app.notes.card {
+        notes.push(Line::from(format!("card saved to {}", card.display())));
+    }
+    for error in &app.notes.save_errors {
+        notes.push(Line::styled(error.clone(), red_bold_style));
+    }
+    notes
 }
 
-fn ui(app: &mut App, f: &mut Frame) {
-    f.render_widget(&app.thok, f.area());
-    if let Some(pos) = ui::cursor_screen_position(&app.thok, f.area()) {
-        f.set_cursor_position(pos);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{
+        backend::TestBackend, buffer::Buffer, crossterm::event::KeyEvent, layout::Position,
+        style::Color,
+    };
+    use std::time::Duration;
+    use thokr::event::Replay;
+
+    const PROMPT: &str = "the quick brown fox";
+
+    fn app(args: &[&str], clock: &FakeClock) -> App {
+        let cli = Cli::parse_from([&["thokr", "--no-history"], args].concat());
+        let markov = cli
+            .markov
+            .map(|unit| Markov::train(lang::builtin_corpus(), unit.into(), cli.order).unwrap());
+        App::new(
+            cli,
+            markov,
+            KeyboardLayout::default(),
+            None,
+            None,
+            Arc::new(clock.clone()),
+        )
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::from(code))
+    }
+
+    /// Types `keys` into a fresh app made from `args`, one key every 200ms,
+    /// then quits; returns the last screen drawn and where the cursor was.
+    fn play(args: &[&str], keys: &str, (width, height): (u16, u16)) -> (Buffer, Option<Position>) {
+        let clock = FakeClock::new();
+        let mut app = app(args, &clock);
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+
+        let every = Duration::from_millis(200);
+        let events = keys
+            .chars()
+            .map(|c| key(KeyCode::Char(c)))
+            .chain([key(KeyCode::Esc)])
+            .fold(Replay::new(clock, []), |replay, event| {
+                replay.then(every, event)
+            });
+        start_tui(&mut terminal, &mut app, events).unwrap();
+
+        let cursor = ui::cursor_screen_position(&app.thok, terminal.get_frame().area());
+        (terminal.backend().buffer().clone(), cursor)
+    }
+
+    /// The text of each row of `buffer`, without trailing blanks.
+    fn screen(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn running_screen() {
+        let (buffer, cursor) = play(&["-p", PROMPT], "the qx", (80, 24));
+        assert_eq!(
+            screen(&buffer),
+            [
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "                               the quick brown fox",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+            ]
+        );
+        // the cursor sits on the next char, right after the mistyped one
+        assert_eq!(cursor, Some(Position::new(37, 11)));
+        assert_eq!(buffer[(36, 11)].symbol(), "u");
+        assert_eq!(buffer[(36, 11)].fg, Color::Red);
+
+        let (buffer, cursor) = play(&["-p", PROMPT], "the qx", (40, 12));
+        assert_eq!(
+            screen(&buffer),
+            [
+                "",
+                "",
+                "",
+                "",
+                "",
+                "           the quick brown fox",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+            ]
+        );
+        assert_eq!(cursor, Some(Position::new(17, 5)));
+
+        // too narrow for one line: the prompt wraps and is left aligned
+        let (buffer, cursor) = play(&["-p", PROMPT], "the quick b", (20, 10));
+        assert_eq!(
+            screen(&buffer),
+            [
+                "",
+                "",
+                "",
+                "",
+                "     the quick",
+                "     brown fox",
+                "",
+                "",
+                "",
+                "",
+            ]
+        );
+        assert_eq!(cursor, Some(Position::new(6, 5)));
+    }
+
+    #[test]
+    fn results_screen() {
+        let (buffer, cursor) = play(&["-p", PROMPT], PROMPT, (80, 24));
+        assert_eq!(
+            screen(&buffer),
+            [
+                "",
+                "",
+                "     72│wpm⠒⠒⠒⠒⠒⠤⠤⠤⠤⠤⣀⣀⣀⣀⣀",
+                "       │                  ⠉⠉⠉⠉⠉⠒⠒⠒⠒⠒⠒⠒⠒⠒⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "       │",
+                "     0 │                                                            seconds",
+                "       └───────────────────────────────────────────────────────────────────",
+                "       1                                                               3.60",
+                "                           64 wpm   100% acc   0.47 sd",
+                "",
+                "     (r)etry / (n)ew / (k)eyboard / (w)ords / (d)iff / (e)xport / (esc)ape",
+                "",
+                "",
+            ]
+        );
+        assert_eq!(cursor, None);
+
+        let (buffer, _) = play(&["-p", PROMPT], PROMPT, (40, 12));
+        assert_eq!(
+            screen(&buffer),
+            [
+                "",
+                "",
+                "     72│⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉⠉",
+                "       └───────────────────────────",
+                "       1                       3.60",
+                "       64 wpm   100% acc   0.47 sd",
+                "",
+                "     (r)etry / (n)ew / (k)eyboard /",
+                "     (w)ords / (d)iff / (e)xport /",
+                "     (esc)ape",
+                "",
+                "",
+            ]
+        );
+
+        // too small for everything, but what's shown isn't cut off mid-word
+        let (buffer, _) = play(&["-p", PROMPT], PROMPT, (20, 10));
+        let rows = screen(&buffer);
+        assert!(rows.iter().any(|row| row.trim() == "64 wpm"));
+        assert!(rows.iter().any(|row| row.trim() == "(r)etry /"));
+    }
+
+    #[test]
+    fn empty_prompts_have_nothing_to_record() {
+        for args in [["-p", ""], ["-w", "0"]] {
+            let (buffer, _) = play(&args, "x", (80, 24));
+            assert!(screen(&buffer)
+                .iter()
+                .any(|row| row.trim() == "0 wpm   0% acc   0.00 sd"));
+        }
+    }
+
+    #[test]
+    fn report_is_of_the_last_finished_test() {
+        let clock = FakeClock::new();
+        let mut app = app(&["-p", "ab", "--json"], &clock);
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let second = Duration::from_secs(1);
+        let events = Replay::new(clock, [])
+            .then(second, key(KeyCode::Char('a')))
+            .then(second, key(KeyCode::Char('b')))
+            .then(second, key(KeyCode::Char('r')))
+            .then(second, key(KeyCode::Char('a')))
+            .then(second, key(KeyCode::Esc));
+        start_tui(&mut terminal, &mut app, events).unwrap();
+
+        // stdout gets the report alone, so `thokr --json | jq` works
+        let mut stdout = vec![];
+        print_report(&app, &mut stdout).unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(report, serde_json::to_value(app.report.unwrap()).unwrap());
+        assert_eq!(report["typed"], "ab");
+        assert_eq!(report["elapsed_secs"], 1.0);
+        assert_eq!(report["wpm"], 24.0);
+        assert_eq!(report["mode"], "prompt");
+    }
+
+    #[test]
+    fn daily_prompts_are_fixed() {
+        for args in [["--daily", "-w", "5"], ["--daily", "-l", "english10k"]] {
+            let err = Cli::try_parse_from([&["thokr"], &args[..]].concat()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn weighting_needs_a_word_list() {
+        for args in [
+            ["--weighted", "-p", "the cat"],
+            ["--weighted", "--daily", "--no-history"],
+            ["--weighted", "--markov", "char"],
+        ] {
+            let err = Cli::try_parse_from([&["thokr"], &args[..]].concat()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+        assert!(!SupportedLanguage::English10k.as_lang().frequency_ordered());
+        assert!(SupportedLanguage::English1k.as_lang().frequency_ordered());
+    }
+
+    #[test]
+    fn timed_out_dailies_dont_count() {
+        let clock = FakeClock::new();
+        let mut app = app(&["--daily", "-s", "1"], &clock);
+        app.thok.write(app.thok.prompt().chars().next().unwrap());
+        clock.advance(Duration::from_secs(1));
+        assert!(app.finish_if_done());
+        assert_eq!(app.notes.daily, None);
+        assert!(app.notes.save_errors[0].starts_with("daily challenge not recorded"));
+    }
+
+    #[test]
+    fn settings_record_the_flags_used() {
+        let clock = FakeClock::new();
+        let app = app(
+            &["-l", "english1k", "--weighted", "--repeat-gap", "4"],
+            &clock,
+        );
+        let settings = &app.settings;
+        assert_eq!(settings.language.as_deref(), Some("english1k"));
+        assert_eq!(
+            (settings.weighted, settings.repeat_gap),
+            (Some(true), Some(4))
+        );
+
+        let app = self::app(
+            &[
+                "--markov",
+                "word",
+                "--order",
+                "2",
+                "--emulate-layout",
+                "dvorak",
+            ],
+            &clock,
+        );
+        let settings = &app.settings;
+        assert_eq!(settings.markov_unit.as_deref(), Some("word"));
+        assert_eq!(settings.markov_order, Some(2));
+        assert_eq!(settings.corpus, None);
+        assert_eq!(settings.emulate_layout.as_deref(), Some("dvorak"));
+        assert_eq!(settings.weighted, None);
+    }
+
+    #[test]
+    fn retries_drill_the_same_lesson() {
+        let clock = FakeClock::new();
+        let mut app = app(&["learn"], &clock);
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let prompt = app.thok.prompt().to_string();
+        let every = Duration::from_millis(50);
+        let events = prompt
+            .chars()
+            .chain(['r'])
+            .chain(prompt.chars())
+            .map(|c| key(KeyCode::Char(c)))
+            .chain([key(KeyCode::Esc)])
+            .fold(Replay::new(clock, []), |replay, event| {
+                replay.then(every, event)
+            });
+        start_tui(&mut terminal, &mut app, events).unwrap();
+
+        // the first run unlocked lesson 2, the retry was lesson 1 again
+        let summary = app.notes.lesson.unwrap();
+        assert_eq!((summary.lesson, summary.passed), (0, true));
+        assert_eq!(summary.unlocked, None);
+        assert_eq!(app.learn.unwrap().unlocked, 1);
+    }
+
+    #[test]
+    fn lessons_pick_their_own_prompt() {
+        for args in [
+            &["--daily", "learn"][..],
+            &["--review", "learn"],
+            &["-f", "2", "learn"],
+            &["--markov", "char", "learn"],
+        ] {
+            let cli = Cli::parse_from([&["thokr"], args].concat());
+            assert!(cli.learn_conflict().is_some(), "{args:?}");
+        }
+        let cli = Cli::parse_from(["thokr", "-p", "fj", "learn"]);
+        assert_eq!(cli.learn_conflict(), Some("--prompt"));
+        assert_eq!(Cli::parse_from(["thokr", "--daily"]).learn_conflict(), None);
+    }
+
+    #[test]
+    fn unreadable_progress_is_left_alone() {
+        let scratch = tempfile::tempdir().unwrap();
+        let progress = scratch.path().join("learn.json");
+        fs::write(&progress, "{\"unlocked\": 3, \"be").unwrap();
+
+        let clock = FakeClock::new();
+        let mut app = App::new(
+            Cli::parse_from(["thokr", "learn"]),
+            None,
+            KeyboardLayout::default(),
+            None,
+            Some(scratch.path().to_path_buf()),
+            Arc::new(clock.clone()),
+        );
+        for c in app.thok.prompt().to_string().chars() {
+            clock.advance(Duration::from_millis(100));
+            app.thok.write(c);
+        }
+        assert!(app.finish_if_done());
+
+        assert_eq!(
+            fs::read_to_string(&progress).unwrap(),
+            "{\"unlocked\": 3, \"be"
+        );
+        assert!(app.notes.save_errors[0].starts_with("lesson progress not saved"));
+    }
+
+    #[test]
+    fn unreadable_review_queue_is_left_alone() {
+        let scratch = tempfile::tempdir().unwrap();
+        let queue = scratch.path().join("review.json");
+        fs::write(&queue, "{\"words\": {\"wh").unwrap();
+
+        let clock = FakeClock::new();
+        let mut app = App::new(
+            Cli::parse_from(["thokr", "-p", "ab"]),
+            None,
+            KeyboardLayout::default(),
+            None,
+            Some(scratch.path().to_path_buf()),
+            Arc::new(clock.clone()),
+        );
+        app.thok.write('x');
+        clock.advance(Duration::from_millis(100));
+        app.thok.write('b');
+        assert!(app.finish_if_done());
+
+        assert_eq!(fs::read_to_string(&queue).unwrap(), "{\"words\": {\"wh");
+        assert!(app.notes.save_errors[0].starts_with("review queue not saved"));
+    }
+
+    #[test]
+    fn locked_lessons_are_rejected() {
+        let clock = FakeClock::new();
+        assert_eq!(
+            app(&["learn", "--lesson", "1"], &clock).locked_lesson(),
+            None
+        );
+        assert_eq!(
+            app(&["learn", "--lesson", "2"], &clock)
+                .locked_lesson()
+                .unwrap(),
+            "lesson 2 is locked: lessons 1 to 1 are unlocked"
+        );
+    }
+
+    #[test]
+    fn export_saves_a_card() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().join("cards");
+        let card_dir = dir.to_str().unwrap();
+        let (buffer, _) = play(&["-p", "ab", "--card-dir", card_dir], "abe", (80, 24));
+
+        let cards = fs::read_dir(&dir).unwrap().collect::<Vec<_>>();
+        assert_eq!(cards.len(), 1);
+        let card = cards[0].as_ref().unwrap().path();
+        assert!(fs::read_to_string(&card).unwrap().contains("wpm</text>"));
+        let note = format!("card saved to {}", card.display());
+        assert!(screen(&buffer).iter().any(|row| row.trim() == note));
+    }
+
+    /// Syncs `app` with its race until `done`.
+    fn sync_until(app: &mut App, done: impl Fn(&race::Race) -> bool) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            app.sync_race();
+            if done(app.race.as_ref().unwrap()) {
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "race timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn race_results_are_ranked() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let clock = FakeClock::new();
+        let mut app = app(&["-p", "ab"], &clock);
+        let setup = race::Setup {
+            prompt: app.thok.prompt().to_string(),
+            number_of_words: 1,
+            number_of_secs: None,
+            settings: app.settings.clone(),
+            racers: 2,
+            countdown_secs: 0,
+        };
+        let server = thread::spawn(move || race::host(listener, setup));
+
+        let (racer, id, setup) = race::Racer::join(addr, "alice").unwrap();
+        app.start_race(racer, id, setup);
+        let (mut bob, _, _) = race::Racer::join(addr, "bob").unwrap();
+        bob.finish(race::Finish {
+            wpm: 200.0,
+            accuracy: 100.0,
+            elapsed_secs: 0.5,
+        })
+        .unwrap();
+        sync_until(&mut app, |race| {
+            race.started() && race.standings.iter().any(|s| s.finish.is_some())
+        });
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 16)).unwrap();
+        let second = Duration::from_secs(1);
+        let events = Replay::new(clock, [])
+            .then(second, key(KeyCode::Char('a')))
+            .then(second, key(KeyCode::Char('b')))
+            .then(second, key(KeyCode::Char('n')))
+            .then(second, key(KeyCode::Esc));
+        start_tui(&mut terminal, &mut app, events).unwrap();
+
+        sync_until(&mut app, race::Race::over);
+        terminal.draw(|f| ui(&mut app, f)).unwrap();
+        let screen = screen(terminal.backend().buffer());
+        let lines = screen.iter().map(|l| l.trim()).collect::<Vec<_>>();
+        assert!(
+            lines.contains(&"1. bob   200 wpm   100% acc"),
+            "{:#?}",
+            screen
+        );
+        assert!(
+            lines.contains(&"2. alice   24 wpm   100% acc"),
+            "{:#?}",
+            screen
+        );
+        // no retrying or moving on to a new prompt in a race
+        assert!(!screen.iter().any(|l| l.contains("(r)etry")));
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn hosting_errors_are_shown() {
+        let clock = FakeClock::new();
+        let mut app = app(&["-p", "the cat"], &clock);
+        app.race = Some(race::Race::default());
+        let hosting = thread::spawn(|| Err(io::Error::other("accept failed")));
+        while !hosting.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        app.hosting = Some(hosting);
+
+        app.share_progress();
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| ui(&mut app, f)).unwrap();
+        assert!(screen(terminal.backend().buffer())
+            .iter()
+            .any(|row| row.trim() == "unable to host the race: accept failed"));
+    }
+
+    #[test]
+    fn races_show_the_countdown_and_opponents() {
+        let clock = FakeClock::new();
+        let mut app = app(&["-p", "the cat"], &clock);
+        let standing = |id, cursor| race::Standing {
+            id,
+            name: format!("racer {}", id),
+            cursor,
+            finish: None,
+            left: false,
+        };
+        app.race = Some(race::Race {
+            racers: 2,
+            countdown: Some(3),
+            standings: vec![standing(0, 0), standing(1, 4)],
+            ..race::Race::default()
+        });
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|f| ui(&mut app, f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = screen(buffer);
+        assert_eq!(rows[3].trim(), "3");
+        assert_eq!(rows[5].trim(), "the cat");
+        // racer 1 is at the "c"
+        let c = rows[5].find('c').unwrap() as u16;
+        assert!(buffer[(c, 5)].modifier.contains(Modifier::UNDERLINED));
+        assert!(!buffer[(c - 1, 5)].modifier.contains(Modifier::UNDERLINED));
+    }
+
+    #[test]
+    fn ports_are_added_unless_named() {
+        assert_eq!(with_port("192.168.1.20", 7878), "192.168.1.20:7878");
+        assert_eq!(with_port("192.168.1.20:9000", 7878), "192.168.1.20:9000");
+        assert_eq!(with_port("::1", 7878), "[::1]:7878");
+        assert_eq!(with_port("[fe80::1]", 7878), "[fe80::1]:7878");
+        assert_eq!(with_port("[::1]:9000", 7878), "[::1]:9000");
+        assert_eq!(with_port("localhost", 7878), "localhost:7878");
+        assert_eq!(with_port("localhost:9000", 7878), "localhost:9000");
     }
 }
@@ -0,0 +1,89 @@
+use directories::{ProjectDirs, UserDirs};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Files older versions of thokr kept in the config dir.
+const LEGACY_FILES: [&str; 5] = [
+    "history.jsonl",
+    "history.corrupt",
+    "log.csv",
+    "log.csv.migrated",
+    "daily.csv",
+];
+
+/// Where thokr keeps the history, daily challenges, review queue and lesson
+/// progress unless told otherwise.
+pub fn default_data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "thokr").map(|proj_dirs| proj_dirs.data_dir().to_path_buf())
+}
+
+/// Where `(e)xport` saves results cards unless told otherwise: the user's
+/// pictures dir, else their home dir.
+pub fn default_card_dir() -> Option<PathBuf> {
+    let user_dirs = UserDirs::new()?;
+    let dir = user_dirs.picture_dir().unwrap_or(user_dirs.home_dir());
+    Some(dir.to_path_buf())
+}
+
+/// Moves what older versions wrote to the config dir over to the default
+/// data dir. Files the data dir already has are left where they are, so this
+/// only ever does something once.
+pub fn migrate_config_dir() -> io::Result<()> {
+    match ProjectDirs::from("", "", "thokr") {
+        Some(proj_dirs) => move_files(proj_dirs.config_dir(), proj_dirs.data_dir()),
+        None => Ok(()),
+    }
+}
+
+fn move_files(from: &Path, to: &Path) -> io::Result<()> {
+    // e.g. macOS, where both are ~/Library/Application Support/thokr
+    if from == to {
+        return Ok(());
+    }
+
+    for name in LEGACY_FILES {
+        let (src, dst) = (from.join(name), to.join(name));
+        if !src.exists() || dst.exists() {
+            continue;
+        }
+        std::fs::create_dir_all(to)?;
+        // rename fails across filesystems, e.g. a separately mounted home
+        if std::fs::rename(&src, &dst).is_err() {
+            std::fs::copy(&src, &dst)?;
+            std::fs::remove_file(&src)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_files_move_once() {
+        let root = tempfile::tempdir().unwrap();
+        let (config, data) = (root.path().join("config"), root.path().join("data"));
+        std::fs::create_dir_all(&config).unwrap();
+        std::fs::write(config.join("log.csv"), "old log").unwrap();
+        std::fs::write(config.join("daily.csv"), "old daily").unwrap();
+        std::fs::write(config.join("settings.toml"), "unrelated").unwrap();
+
+        move_files(&config, &data).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(data.join("log.csv")).unwrap(),
+            "old log"
+        );
+        assert!(data.join("daily.csv").exists());
+        assert!(!config.join("log.csv").exists());
+        assert!(config.join("settings.toml").exists());
+
+        // whatever is already in the data dir wins
+        std::fs::write(config.join("daily.csv"), "stale daily").unwrap();
+        move_files(&config, &data).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(data.join("daily.csv")).unwrap(),
+            "old daily"
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use crate::event::{EventSource, WakeOn};
+use crate::history::Settings;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// port `thokr host` listens on unless told otherwise
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// seconds of countdown once everyone has joined
+pub const COUNTDOWN_SECS: u64 = 3;
+
+/// how long a new connection gets to say who it is, and a racer gets to be
+/// welcomed
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// longest message either side reads, prompt included; the host listens on
+/// the LAN, so anyone there could otherwise make it buffer without end
+const MAX_MESSAGE_BYTES: u64 = 1 << 20;
+
+/// The race a host runs: everyone types the same prompt.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Setup {
+    pub prompt: String,
+    pub number_of_words: usize,
+    pub number_of_secs: Option<f64>,
+    /// how the prompt was made, recorded in every racer's history
+    pub settings: Settings,
+    /// racers the countdown waits for, the host included
+    pub racers: usize,
+    pub countdown_secs: u64,
+}
+
+/// A racer's finished test.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Finish {
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub elapsed_secs: f64,
+}
+
+/// How far along a racer is, as relayed by the host.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub id: usize,
+    pub name: String,
+    /// chars typed so far
+    pub cursor: usize,
+    pub finish: Option<Finish>,
+    /// disconnected from the host
+    pub left: bool,
+}
+
+/// What the host and racers tell each other, one JSON object per line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// racer → host: first thing sent, to race under `name`
+    Join { name: String },
+    /// host → racer: the answer to `Join`
+    Welcome { id: usize, setup: Box<Setup> },
+    /// host → racers: seconds left until the start, 0 being the start
+    Countdown { secs: u64 },
+    /// host → racers: where everyone is, whenever that changes
+    Standings { standings: Vec<Standing> },
+    /// racer → host
+    Progress { cursor: usize },
+    /// racer → host
+    Finished(Finish),
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+/// The next message, or None once the other side hung up.
+fn receive(reader: &mut impl BufRead) -> io::Result<Option<Message>> {
+    let mut line = String::new();
+    let read = reader
+        .by_ref()
+        .take(MAX_MESSAGE_BYTES)
+        .read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') && read as u64 == MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too long",
+        ));
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Sends `message` to every racer still connected.
+fn broadcast(racers: &mut [Option<TcpStream>], message: &Message) {
+    for racer in racers {
+        if racer.as_mut().is_some_and(|r| send(r, message).is_err()) {
+            *racer = None;
+        }
+    }
+}
+
+/// Runs a race on `listener`: waits for `setup.racers` racers to join,
+/// counts down, then relays everyone's progress until all of them have
+/// finished or left. Racers are hung up on when it returns, error or not.
+pub fn host(listener: TcpListener, setup: Setup) -> io::Result<()> {
+    let mut racers: Vec<Option<TcpStream>> = vec![];
+    let hosted = match gather(&listener, &setup, &mut racers) {
+        Ok((rx, standings)) => {
+            // the race is full: stop listening so anyone joining late is
+            // refused instead of left waiting
+            drop(listener);
+            relay(&setup, &mut racers, rx, standings)
+        }
+        Err(e) => Err(e),
+    };
+
+    // hang up, which the threads reading from each racer are waiting for too
+    for racer in racers.iter().flatten() {
+        let _ = racer.shutdown(Shutdown::Both);
+    }
+    hosted
+}
+
+/// what each racer sends, by racer id; None once they've left
+type Received = mpsc::Receiver<(usize, Option<Message>)>;
+
+/// Welcomes racers until `setup.racers` have joined; returns what they send
+/// from then on and their standings.
+fn gather(
+    listener: &TcpListener,
+    setup: &Setup,
+    racers: &mut Vec<Option<TcpStream>>,
+) -> io::Result<(Received, Vec<Standing>)> {
+    let (tx, rx) = mpsc::channel();
+    let mut standings: Vec<Standing> = vec![];
+
+    while racers.len() < setup.racers {
+        let (mut stream, _) = listener.accept()?;
+        let joined = stream
+            .set_read_timeout(Some(JOIN_TIMEOUT))
+            .and_then(|_| stream.try_clone())
+            .map(BufReader::new)
+            .and_then(|mut reader| Ok((receive(&mut reader)?, reader)));
+        let (name, mut reader) = match joined {
+            Ok((Some(Message::Join { name }), reader)) => (name, reader),
+            // not a racer, or too slow to say so
+            _ => continue,
+        };
+
+        let id = racers.len();
+        let welcome = Message::Welcome {
+            id,
+            setup: Box::new(setup.clone()),
+        };
+        if stream
+            .set_read_timeout(None)
+            .and_then(|_| send(&mut stream, &welcome))
+            .is_err()
+        {
+            continue;
+        }
+
+        let tx = tx.clone();
+        thread::spawn(move || loop {
+            let message = receive(&mut reader).ok().flatten();
+            let left = message.is_none();
+            if tx.send((id, message)).is_err() || left {
+                break;
+            }
+        });
+
+        racers.push(Some(stream));
+        standings.push(Standing {
+            id,
+            name,
+            cursor: 0,
+            finish: None,
+            left: false,
+        });
+        broadcast(
+            racers,
+            &Message::Standings {
+                standings: standings.clone(),
+            },
+        );
+    }
+    Ok((rx, standings))
+}
+
+/// Counts down, then relays everyone's progress until all of them have
+/// finished or left.
+fn relay(
+    setup: &Setup,
+    racers: &mut [Option<TcpStream>],
+    rx: Received,
+    mut standings: Vec<Standing>,
+) -> io::Result<()> {
+    for secs in (0..=setup.countdown_secs).rev() {
+        broadcast(racers, &Message::Countdown { secs });
+        if secs > 0 {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    while standings.iter().any(|s| s.finish.is_none() && !s.left) {
+        let Ok((id, message)) = rx.recv() else {
+            break;
+        };
+        let standing = &mut standings[id];
+        match message {
+            Some(Message::Progress { cursor }) => standing.cursor = cursor,
+            Some(Message::Finished(finish)) => standing.finish = Some(finish),
+            Some(_) => continue,
+            None => standing.left = true,
+        }
+        broadcast(
+            racers,
+            &Message::Standings {
+                standings: standings.clone(),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// What a racer's screen shows of the race.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Race {
+    /// id of this racer
+    pub me: usize,
+    /// racers the countdown waits for
+    pub racers: usize,
+    /// seconds left until the start; None until everyone has joined
+    pub countdown: Option<u64>,
+    pub standings: Vec<Standing>,
+    /// the connection to the host was lost
+    pub disconnected: bool,
+    /// why hosting failed, on the host's own screen
+    pub host_error: Option<String>,
+}
+
+impl Race {
+    pub fn started(&self) -> bool {
+        self.countdown == Some(0)
+    }
+
+    /// Whether everyone has finished or left.
+    pub fn over(&self) -> bool {
+        self.standings.len() == self.racers
+            && self.standings.iter().all(|s| s.finish.is_some() || s.left)
+    }
+
+    /// Everyone but this racer.
+    pub fn opponents(&self) -> impl Iterator<Item = &Standing> {
+        self.standings.iter().filter(move |s| s.id != self.me)
+    }
+
+    /// Racers who finished, fastest (then most accurate) first, followed by
+    /// those still typing by how far they got.
+    pub fn ranking(&self) -> Vec<&Standing> {
+        let mut ranking = self.standings.iter().collect::<Vec<&Standing>>();
+        ranking.sort_by(|a, b| match (&a.finish, &b.finish) {
+            (Some(a), Some(b)) => b
+                .wpm
+                .total_cmp(&a.wpm)
+                .then(b.accuracy.total_cmp(&a.accuracy)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => b.cursor.cmp(&a.cursor),
+        });
+        ranking
+    }
+}
+
+/// messages from the host not yet applied to the race; None marks the end of
+/// the connection
+type Inbox = Arc<Mutex<VecDeque<Option<Message>>>>;
+
+/// A racer's connection to the host.
+#[derive(Debug)]
+pub struct Racer {
+    stream: TcpStream,
+    inbox: Inbox,
+    /// last cursor reported to the host
+    sent_cursor: usize,
+    sent_finish: bool,
+}
+
+impl Racer {
+    /// Joins the race hosted at `addr` as `name`; returns the racer's id and
+    /// the race to type.
+    pub fn join(addr: impl ToSocketAddrs, name: &str) -> io::Result<(Self, usize, Setup)> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        send(
+            &mut stream,
+            &Message::Join {
+                name: name.to_string(),
+            },
+        )?;
+
+        // a host whose race is full or under way never answers
+        stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let welcome = match receive(&mut reader) {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the host didn't answer: the race may be full or under way",
+                ))
+            }
+            welcome => welcome?,
+        };
+        let (id, setup) = match welcome {
+            Some(Message::Welcome { id, setup }) => (id, *setup),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the host didn't let us join",
+                ))
+            }
+        };
+        stream.set_read_timeout(None)?;
+
+        let inbox = Inbox::default();
+        let messages = inbox.clone();
+        thread::spawn(move || loop {
+            let message = receive(&mut reader).ok().flatten();
+            let hung_up = message.is_none();
+            messages.lock().unwrap().push_back(message);
+            if hung_up {
+                break;
+            }
+        });
+
+        Ok((
+            Self {
+                stream,
+                inbox,
+                sent_cursor: 0,
+                sent_finish: false,
+            },
+            id,
+            setup,
+        ))
+    }
+
+    /// Applies what the host sent since the last update to `race`.
+    pub fn update(&self, race: &mut Race) {
+        for message in self.inbox.lock().unwrap().drain(..) {
+            match message {
+                Some(Message::Countdown { secs }) => race.countdown = Some(secs),
+                Some(Message::Standings { standings }) => race.standings = standings,
+                Some(_) => {}
+                None => race.disconnected = true,
+            }
+        }
+    }
+
+    /// Tells the host how many chars have been typed, if that changed.
+    pub fn progress(&mut self, cursor: usize) -> io::Result<()> {
+        if cursor == self.sent_cursor || self.sent_finish {
+            return Ok(());
+        }
+        send(&mut self.stream, &Message::Progress { cursor })?;
+        self.sent_cursor = cursor;
+        Ok(())
+    }
+
+    /// Tells the host the test is done, once.
+    pub fn finish(&mut self, finish: Finish) -> io::Result<()> {
+        if self.sent_finish {
+            return Ok(());
+        }
+        send(&mut self.stream, &Message::Finished(finish))?;
+        self.sent_finish = true;
+        Ok(())
+    }
+
+    /// `events`, interrupted whenever the host sends something so the race
+    /// can be redrawn.
+    pub fn events<S: EventSource>(&self, events: S) -> WakeOn<S> {
+        let inbox = self.inbox.clone();
+        WakeOn::new(events, move || !inbox.lock().unwrap().is_empty())
+    }
+}
+
+impl Drop for Racer {
+    fn drop(&mut self) {
+        // the thread reading from the host holds a clone of the stream
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn setup(racers: usize) -> Setup {
+        Setup {
+            prompt: String::from("the cat"),
+            number_of_words: 2,
+            number_of_secs: None,
+            settings: Settings::default(),
+            racers,
+            countdown_secs: 0,
+        }
+    }
+
+    /// Waits until `racer` has heard from the host and `done` holds.
+    fn wait_for(racer: &Racer, race: &mut Race, done: impl Fn(&Race) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(race) {
+            assert!(Instant::now() < deadline, "timed out at {:?}", race);
+            thread::sleep(Duration::from_millis(5));
+            racer.update(race);
+        }
+    }
+
+    fn standing(id: usize, cursor: usize, wpm: Option<f64>) -> Standing {
+        Standing {
+            id,
+            name: format!("racer {}", id),
+            cursor,
+            finish: wpm.map(|wpm| Finish {
+                wpm,
+                accuracy: 100.0,
+                elapsed_secs: 10.0,
+            }),
+            left: false,
+        }
+    }
+
+    #[test]
+    fn race_on_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || host(listener, setup(2)));
+
+        let (mut alice, alice_id, alice_setup) = Racer::join(addr, "alice").unwrap();
+        let (mut bob, bob_id, bob_setup) = Racer::join(addr, "bob").unwrap();
+        assert_eq!((alice_id, bob_id), (0, 1));
+        assert_eq!(alice_setup, setup(2));
+        assert_eq!(bob_setup, alice_setup);
+
+        let mut race = Race {
+            me: alice_id,
+            racers: 2,
+            ..Race::default()
+        };
+        wait_for(&alice, &mut race, |race| race.started());
+        assert_eq!(race.standings.len(), 2);
+
+        bob.progress(3).unwrap();
+        wait_for(&alice, &mut race, |race| race.standings[1].cursor == 3);
+
+        let finish = |wpm| Finish {
+            wpm,
+            accuracy: 100.0,
+            elapsed_secs: 1.0,
+        };
+        alice.progress(7).unwrap();
+        alice.finish(finish(80.0)).unwrap();
+        bob.finish(finish(90.0)).unwrap();
+        wait_for(&alice, &mut race, Race::over);
+
+        let ranking = race.ranking();
+        assert_eq!(ranking[0].name, "bob");
+        assert_eq!(ranking[1].name, "alice");
+        assert_eq!(ranking[1].cursor, 7);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn racers_who_leave_are_done() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || host(listener, setup(2)));
+
+        let (alice, _, _) = Racer::join(addr, "alice").unwrap();
+        let (bob, _, _) = Racer::join(addr, "bob").unwrap();
+        let mut race = Race {
+            racers: 2,
+            ..Race::default()
+        };
+        wait_for(&alice, &mut race, |race| race.started());
+        drop(bob);
+        drop(alice);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn late_joiners_are_turned_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || host(listener, setup(1)));
+
+        let (alice, _, _) = Racer::join(addr, "alice").unwrap();
+        let mut race = Race {
+            racers: 1,
+            ..Race::default()
+        };
+        wait_for(&alice, &mut race, |race| race.started());
+
+        let joined = std::time::Instant::now();
+        assert!(Racer::join(addr, "bob").is_err());
+        assert!(joined.elapsed() < JOIN_TIMEOUT);
+        drop(alice);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn ranking_puts_finishers_first() {
+        let race = Race {
+            me: 0,
+            racers: 4,
+            countdown: Some(0),
+            standings: vec![
+                standing(0, 3, None),
+                standing(1, 7, Some(60.0)),
+                standing(2, 5, None),
+                standing(3, 7, Some(75.0)),
+            ],
+            disconnected: false,
+            host_error: None,
+        };
+        let ids = race.ranking().iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids, [3, 1, 2, 0]);
+        assert!(!race.over());
+        assert_eq!(race.opponents().count(), 3);
+    }
+
+    #[test]
+    fn oversized_messages_are_refused() {
+        let flood = vec![b'a'; MAX_MESSAGE_BYTES as usize + 1];
+        let error = receive(&mut io::Cursor::new(flood)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
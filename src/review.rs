@@ -0,0 +1,272 @@
+use crate::history;
+use chrono::{Duration, Local, NaiveDate};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// ease a new card starts with (SM-2's default)
+const INITIAL_EASE: f64 = 2.5;
+/// ease never drops below this, so hard words still spread out over time
+const MIN_EASE: f64 = 1.3;
+/// words reviewed correctly at this interval are considered learned
+const RETIRE_AFTER_DAYS: i64 = 90;
+
+/// Spaced-repetition state of a single mistyped word.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub due: NaiveDate,
+    /// days until the next review after a correct one
+    pub interval_days: i64,
+    pub ease: f64,
+    pub lapses: u32,
+}
+
+/// Every word that is being drilled, persisted in the data dir.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewStore {
+    pub words: BTreeMap<String, Card>,
+}
+
+/// What the results screen shows about the review queue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReviewSummary {
+    /// words mistyped in this test
+    pub mistakes: usize,
+    /// words due for review after this test
+    pub due: usize,
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// `word` as it's drilled: lowercase, without the punctuation around it, so
+/// "The," and "the" are the same word while "don't" keeps its apostrophe.
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+impl ReviewStore {
+    /// The queue saved in `dir`, or an empty one if nothing was saved.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        match std::fs::read_to_string(dir.join("review.json")) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        history::replace(
+            &dir.join("review.json"),
+            &serde_json::to_string_pretty(self)?,
+        )
+    }
+
+    /// Applies the (word, mistyped) outcomes of a test. A mistake (re)starts
+    /// a word's schedule; typing a due word correctly pushes it further out
+    /// until it is retired. Words are compared by `normalize`, and each counts
+    /// once per test: mistyped if any of its occurrences was.
+    pub fn record(&mut self, outcomes: &[(String, bool)], today: NaiveDate) -> ReviewSummary {
+        let mut per_word: BTreeMap<String, bool> = BTreeMap::new();
+        for (word, mistyped) in outcomes {
+            let word = normalize(word);
+            if !word.is_empty() {
+                *per_word.entry(word).or_default() |= mistyped;
+            }
+        }
+
+        let mut mistakes = 0;
+        for (word, mistyped) in per_word {
+            if mistyped {
+                mistakes += 1;
+                let card = self.words.entry(word).or_insert(Card {
+                    due: today,
+                    interval_days: 0,
+                    ease: INITIAL_EASE,
+                    lapses: 0,
+                });
+                card.due = today;
+                card.interval_days = 0;
+                card.ease = (card.ease - 0.2).max(MIN_EASE);
+                card.lapses += 1;
+            } else if let Some(card) = self.words.get_mut(&word) {
+                if card.due > today {
+                    continue;
+                }
+                card.interval_days = match card.interval_days {
+                    0 => 1,
+                    1 => 3,
+                    n => (n as f64 * card.ease).round() as i64,
+                };
+                if card.interval_days >= RETIRE_AFTER_DAYS {
+                    self.words.remove(&word);
+                } else {
+                    card.due = today + Duration::days(card.interval_days);
+                }
+            }
+        }
+
+        ReviewSummary {
+            mistakes,
+            due: self.due(today).len(),
+        }
+    }
+
+    /// Words due on `today`, most overdue first.
+    pub fn due(&self, today: NaiveDate) -> Vec<&str> {
+        let mut due = self
+            .words
+            .iter()
+            .filter(|(_, card)| card.due <= today)
+            .collect::<Vec<_>>();
+        due.sort_by_key(|(_, card)| card.due);
+        due.into_iter().map(|(word, _)| word.as_str()).collect()
+    }
+
+    /// A prompt of `num` due words, cycling through them in random order when
+    /// fewer than `num` are due.
+    pub fn prompt<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        today: NaiveDate,
+        num: usize,
+    ) -> Vec<String> {
+        let due = self.due(today);
+        let mut words = Vec::with_capacity(num);
+        while !due.is_empty() && words.len() < num {
+            let mut round = due.clone();
+            round.shuffle(rng);
+            words.extend(round.into_iter().take(num - words.len()).map(String::from));
+        }
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn outcome(word: &str, mistyped: bool) -> (String, bool) {
+        (word.to_string(), mistyped)
+    }
+
+    #[test]
+    fn mistakes_are_due_immediately() {
+        let mut store = ReviewStore::default();
+        let summary = store.record(
+            &[outcome("the", false), outcome("which", true)],
+            day("2022-05-01"),
+        );
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                mistakes: 1,
+                due: 1
+            }
+        );
+        assert_eq!(store.due(day("2022-05-01")), vec!["which"]);
+    }
+
+    #[test]
+    fn words_count_once_per_test() {
+        let mut store = ReviewStore::default();
+        let summary = store.record(
+            &[
+                outcome("The", true),
+                outcome("the,", true),
+                outcome("\"the\"", false),
+                outcome("don't", true),
+                outcome("--", true),
+            ],
+            day("2022-05-01"),
+        );
+        assert_eq!(summary.mistakes, 2);
+        assert_eq!(store.words["the"].lapses, 1);
+        assert_eq!(store.words["don't"].lapses, 1);
+        assert_eq!(store.words.len(), 2);
+
+        // hitting a due word twice only moves it out once
+        store.record(
+            &[outcome("the", false), outcome("the", false)],
+            day("2022-05-01"),
+        );
+        assert_eq!(store.words["the"].interval_days, 1);
+    }
+
+    #[test]
+    fn correct_reviews_space_out() {
+        let mut store = ReviewStore::default();
+        store.record(&[outcome("which", true)], day("2022-05-01"));
+
+        store.record(&[outcome("which", false)], day("2022-05-01"));
+        assert_eq!(store.words["which"].due, day("2022-05-02"));
+
+        // typing it correctly before it's due doesn't count
+        store.record(&[outcome("which", false)], day("2022-05-01"));
+        assert_eq!(store.words["which"].due, day("2022-05-02"));
+
+        store.record(&[outcome("which", false)], day("2022-05-02"));
+        assert_eq!(store.words["which"].due, day("2022-05-05"));
+
+        store.record(&[outcome("which", false)], day("2022-05-05"));
+        assert_eq!(store.words["which"].interval_days, 7);
+
+        // another mistake starts over with a lower ease
+        store.record(&[outcome("which", true)], day("2022-05-12"));
+        let card = &store.words["which"];
+        assert_eq!(card.due, day("2022-05-12"));
+        assert_eq!(card.lapses, 2);
+        assert!(card.ease < INITIAL_EASE - 0.2);
+    }
+
+    #[test]
+    fn learned_words_are_retired() {
+        let mut store = ReviewStore::default();
+        store.record(&[outcome("which", true)], day("2022-05-01"));
+        let mut today = day("2022-05-01");
+        while let Some(card) = store.words.get("which") {
+            today = card.due;
+            store.record(&[outcome("which", false)], today);
+        }
+        assert!(today < day("2023-01-01"));
+    }
+
+    #[test]
+    fn prompt_cycles_through_due_words() {
+        let mut store = ReviewStore::default();
+        store.record(&[outcome("a", true), outcome("b", true)], day("2022-05-01"));
+        let words = store.prompt(&mut StdRng::seed_from_u64(1), day("2022-05-01"), 5);
+        assert_eq!(words.len(), 5);
+        let a = words.iter().filter(|w| *w == "a").count();
+        assert!(a == 2 || a == 3, "{words:?}");
+        assert!(store
+            .prompt(&mut StdRng::seed_from_u64(1), day("2022-04-01"), 5)
+            .is_empty());
+    }
+
+    #[test]
+    fn queue_survives_a_round_trip() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        assert_eq!(ReviewStore::load(dir).unwrap(), ReviewStore::default());
+
+        let mut store = ReviewStore::default();
+        store.record(&[outcome("which", true)], day("2022-05-01"));
+        store.save(dir).unwrap();
+        assert_eq!(ReviewStore::load(dir).unwrap(), store);
+
+        std::fs::write(dir.join("review.json"), "{\"words\": {\"wh").unwrap();
+        assert!(ReviewStore::load(dir).is_err());
+    }
+}
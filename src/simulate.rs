@@ -0,0 +1,164 @@
+use crate::clock::FakeClock;
+use crate::thok::{Results, Thok};
+use std::io;
+use std::time::Duration;
+
+/// A key of a scripted test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+}
+
+/// `key` pressed `at` after the timeline starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keystroke {
+    pub at: Duration,
+    pub key: Key,
+}
+
+/// Reads a keystroke timeline: one `<offset ms> <key>` per line, where the
+/// key is a single char, `space` or `backspace`. Offsets never go back in
+/// time; blank lines and lines starting with `#` are skipped.
+pub fn parse_timeline(contents: &str) -> io::Result<Vec<Keystroke>> {
+    let mut timeline: Vec<Keystroke> = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |msg: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", i + 1, msg),
+            )
+        };
+
+        let (ms, key) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid(format!("expected `<offset ms> <key>`, got `{}`", line)))?;
+        let at = Duration::from_millis(
+            ms.parse()
+                .map_err(|_| invalid(format!("`{}` is not an offset in ms", ms)))?,
+        );
+        let key = match key.trim() {
+            "space" => Key::Char(' '),
+            "backspace" => Key::Backspace,
+            key => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Key::Char(c),
+                    _ => return Err(invalid(format!("`{}` is not a single key", key))),
+                }
+            }
+        };
+        if timeline.last().is_some_and(|last| at < last.at) {
+            return Err(invalid(String::from("offsets must not decrease")));
+        }
+
+        timeline.push(Keystroke { at, key });
+    }
+
+    Ok(timeline)
+}
+
+/// Plays `timeline` into `thok` the way a person at the keyboard would,
+/// moving `clock` (the one `thok` was made with) to each keystroke, and
+/// returns the results. Keys after the test is over are ignored; a timed
+/// test runs until its deadline.
+pub fn run(thok: &mut Thok, clock: &FakeClock, timeline: &[Keystroke]) -> Results {
+    let origin = clock.elapsed();
+
+    for keystroke in timeline {
+        clock.set(origin + keystroke.at);
+        if thok.has_finished() {
+            break;
+        }
+        match keystroke.key {
+            Key::Char(c) => thok.write(c),
+            Key::Backspace => thok.backspace(),
+        }
+    }
+
+    if let Some(remaining) = thok.seconds_remaining().filter(|_| thok.has_started()) {
+        clock.advance(Duration::from_secs_f64(remaining.max(0.0)));
+    }
+    if thok.has_started() {
+        thok.calc_results();
+    }
+    thok.results()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn thok(prompt: &str, secs: Option<f64>, clock: &FakeClock) -> Thok {
+        Thok::with_clock(
+            prompt.to_string(),
+            prompt.split(' ').count(),
+            secs,
+            Arc::new(clock.clone()),
+        )
+    }
+
+    #[test]
+    fn timeline_format() {
+        let timeline = parse_timeline("# ms key\n0 h\n\n120 space\n200 backspace\n").unwrap();
+        assert_eq!(
+            timeline,
+            vec![
+                Keystroke {
+                    at: Duration::ZERO,
+                    key: Key::Char('h')
+                },
+                Keystroke {
+                    at: Duration::from_millis(120),
+                    key: Key::Char(' ')
+                },
+                Keystroke {
+                    at: Duration::from_millis(200),
+                    key: Key::Backspace
+                },
+            ]
+        );
+
+        let err = parse_timeline("0 a\n10 ab\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: `ab` is not a single key");
+        assert!(parse_timeline("10 a\n5 b\n").is_err());
+        assert!(parse_timeline("x a\n").is_err());
+    }
+
+    #[test]
+    fn scripted_test_is_exact() {
+        let clock = FakeClock::new();
+        let mut thok = thok("ab cd", None, &clock);
+        let timeline =
+            parse_timeline("0 a\n400 x\n600 backspace\n800 b\n1000 space\n1500 c\n2000 d\n")
+                .unwrap();
+
+        let results = run(&mut thok, &clock, &timeline);
+        assert!(results.finished);
+        assert_eq!(results.elapsed_secs, 2.0);
+        assert_eq!(results.wpm, 30.0);
+        assert_eq!(results.raw_wpm, 30.0);
+        assert_eq!(results.accuracy, 100.0);
+        assert_eq!(results.corrected, 1);
+        assert_eq!(results.words[1].hesitation_ms, 500.0);
+    }
+
+    #[test]
+    fn timed_test_runs_to_its_deadline() {
+        let clock = FakeClock::new();
+        let mut thok = thok("abcdef", Some(1.0), &clock);
+        let timeline = parse_timeline("0 a\n500 b\n1500 c\n").unwrap();
+
+        let results = run(&mut thok, &clock, &timeline);
+        assert!(results.finished);
+        assert_eq!(results.typed, "ab");
+        assert_eq!(results.elapsed_secs, 1.0);
+        assert_eq!(results.raw_wpm, 24.0);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::event::{EventSource, WakeOn};
+use crate::thok::{Input, Outcome, Thok};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter;
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// port `--publish` and `thokr watch` use unless told otherwise
+pub const DEFAULT_PORT: u16 = 7879;
+
+/// address `--publish` and `thokr watch` use unless told otherwise
+pub const DEFAULT_ADDR: &str = "127.0.0.1:7879";
+
+/// how long a watcher may take to accept a snapshot before it's dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// longest snapshot a watcher reads; roomier than a race message since it
+/// holds every typed char as well as the prompt, but still a bound on what a
+/// misbehaving publisher can make it buffer
+const MAX_SNAPSHOT_BYTES: u64 = 16 << 20;
+
+/// What watchers see of the test being typed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub prompt: String,
+    /// every char typed so far and whether it was right
+    pub typed: Vec<(char, Outcome)>,
+    pub cursor: usize,
+    pub number_of_secs: Option<f64>,
+    /// None until the first key
+    pub elapsed_secs: Option<f64>,
+    pub pace_wpm: Option<f64>,
+    /// set once the test is over
+    pub scores: Option<Scores>,
+}
+
+/// What the results screen of a finished test shows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scores {
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub std_dev: f64,
+    pub wpm_coords: Vec<(f64, f64)>,
+}
+
+impl Snapshot {
+    pub fn of(thok: &Thok) -> Self {
+        Self {
+            prompt: thok.prompt.clone(),
+            typed: thok.input.iter().map(|i| (i.char, i.outcome)).collect(),
+            cursor: thok.cursor_pos,
+            number_of_secs: thok.number_of_secs,
+            elapsed_secs: thok.elapsed_secs(),
+            pace_wpm: thok.pace_wpm,
+            scores: thok.has_finished().then(|| Scores {
+                wpm: thok.wpm,
+                accuracy: thok.accuracy,
+                std_dev: thok.std_dev,
+                wpm_coords: thok.wpm_coords.clone(),
+            }),
+        }
+    }
+
+    /// The test as of the snapshot, for drawing; its timer runs on from
+    /// there.
+    pub fn to_thok(&self) -> Thok {
+        let now = Instant::now();
+        let mut thok = Thok::new(
+            self.prompt.clone(),
+            self.prompt.split_whitespace().count(),
+            self.number_of_secs,
+        );
+        thok.input = self
+            .typed
+            .iter()
+            .map(|&(char, outcome)| Input {
+                char,
+                outcome,
+                timestamp: now,
+            })
+            .collect();
+        thok.cursor_pos = self.cursor;
+        thok.pace_wpm = self.pace_wpm;
+        thok.started_at = self
+            .elapsed_secs
+            .and_then(|secs| now.checked_sub(Duration::from_secs_f64(secs)));
+        if let Some(scores) = &self.scores {
+            thok.wpm = scores.wpm;
+            thok.accuracy = scores.accuracy;
+            thok.std_dev = scores.std_dev;
+            thok.wpm_coords = scores.wpm_coords.clone();
+        }
+        thok
+    }
+}
+
+/// What the thread writing to watchers is handed.
+#[derive(Debug)]
+enum Outgoing {
+    Watcher(TcpStream),
+    /// a snapshot, serialized, newline included
+    Snapshot(String),
+}
+
+/// Sends the test being typed to everyone watching it, one JSON
+/// [`Snapshot`] per line.
+#[derive(Debug)]
+pub struct Publisher {
+    outgoing: Sender<Outgoing>,
+    /// the last snapshot published
+    last: Option<Snapshot>,
+}
+
+impl Publisher {
+    /// Lets watchers connect to `listener` from now on. Watchers are written
+    /// to on a thread of their own, so a stalled one can't hold up typing.
+    pub fn new(listener: TcpListener) -> Self {
+        let (outgoing, received) = mpsc::channel();
+        let incoming = outgoing.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if incoming.send(Outgoing::Watcher(stream)).is_err() {
+                    break;
+                }
+            }
+        });
+        thread::spawn(move || write_to_watchers(received));
+        Self {
+            outgoing,
+            last: None,
+        }
+    }
+
+    /// Sends `snapshot` to everyone watching, unless only its time moved on
+    /// since the last one: watchers keep the clock running themselves.
+    pub fn publish(&mut self, snapshot: &Snapshot) {
+        let unchanged = self.last.as_ref().is_some_and(|last| {
+            *snapshot
+                == Snapshot {
+                    elapsed_secs: snapshot.elapsed_secs,
+                    ..last.clone()
+                }
+        });
+        if unchanged {
+            return;
+        }
+
+        let mut line = serde_json::to_string(snapshot).expect("snapshots are plain data");
+        line.push('\n');
+        // the writer only stops along with the process
+        let _ = self.outgoing.send(Outgoing::Snapshot(line));
+        self.last = Some(snapshot.clone());
+    }
+}
+
+/// Welcomes new watchers with the latest snapshot and sends every snapshot
+/// to all of them, skipping to the newest when they come in faster than
+/// they can be written. Watchers that can't keep up are dropped.
+fn write_to_watchers(received: Receiver<Outgoing>) {
+    let mut streams: Vec<TcpStream> = vec![];
+    let mut latest: Option<String> = None;
+
+    while let Ok(first) = received.recv() {
+        let mut newest = None;
+        for outgoing in iter::once(first).chain(received.try_iter()) {
+            match outgoing {
+                Outgoing::Watcher(mut stream) => {
+                    let welcomed =
+                        stream
+                            .set_write_timeout(Some(WRITE_TIMEOUT))
+                            .and_then(|_| match &latest {
+                                Some(line) => stream.write_all(line.as_bytes()),
+                                None => Ok(()),
+                            });
+                    if welcomed.is_ok() {
+                        streams.push(stream);
+                    }
+                }
+                Outgoing::Snapshot(line) => newest = Some(line),
+            }
+        }
+
+        if let Some(line) = newest {
+            streams.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+            latest = Some(line);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inbox {
+    /// the newest snapshot not yet taken
+    latest: Option<Snapshot>,
+    hung_up: bool,
+    /// something happened since the last `take`
+    fresh: bool,
+}
+
+/// Reads the next line from a publisher into `line`, returning its length;
+/// 0 once the publisher hangs up.
+fn read_snapshot_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let read = reader.by_ref().take(MAX_SNAPSHOT_BYTES).read_line(line)?;
+    if !line.ends_with('\n') && read as u64 == MAX_SNAPSHOT_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "snapshot too long",
+        ));
+    }
+    Ok(read)
+}
+
+/// The receiving end of a [`Publisher`].
+#[derive(Debug)]
+pub struct Watcher {
+    stream: TcpStream,
+    inbox: Arc<Mutex<Inbox>>,
+}
+
+impl Watcher {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+        let received = inbox.clone();
+
+        thread::spawn(move || {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let snapshot = match read_snapshot_line(&mut reader, &mut line) {
+                    Ok(0) | Err(_) => None,
+                    // skip whatever doesn't parse rather than stop watching
+                    Ok(_) => match serde_json::from_str(&line) {
+                        Ok(snapshot) => Some(snapshot),
+                        Err(_) => continue,
+                    },
+                };
+
+                let mut inbox = received.lock().unwrap();
+                inbox.fresh = true;
+                match snapshot {
+                    Some(snapshot) => inbox.latest = Some(snapshot),
+                    None => {
+                        inbox.hung_up = true;
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { stream, inbox })
+    }
+
+    /// The newest snapshot received since the last call, if any.
+    pub fn take(&self) -> Option<Snapshot> {
+        let mut inbox = self.inbox.lock().unwrap();
+        inbox.fresh = false;
+        inbox.latest.take()
+    }
+
+    /// Whether the publisher stopped publishing.
+    pub fn hung_up(&self) -> bool {
+        self.inbox.lock().unwrap().hung_up
+    }
+
+    /// `events`, interrupted whenever a snapshot comes in so it can be drawn.
+    pub fn events<S: EventSource>(&self, events: S) -> WakeOn<S> {
+        let inbox = self.inbox.clone();
+        WakeOn::new(events, move || inbox.lock().unwrap().fresh)
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // the thread reading snapshots holds a clone of the stream
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+    /// Waits until `watcher` has received a snapshot.
+    fn next(watcher: &Watcher) -> Snapshot {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(snapshot) = watcher.take() {
+                return snapshot;
+            }
+            assert!(Instant::now() < deadline, "no snapshot came in");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn watchers_see_the_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut publisher = Publisher::new(listener);
+
+        let mut thok = Thok::new(String::from("ab"), 1, None);
+        thok.write('x');
+        publisher.publish(&Snapshot::of(&thok));
+
+        // a late watcher starts with the latest snapshot
+        let watcher = Watcher::connect(addr).unwrap();
+        let snapshot = next(&watcher);
+        assert_eq!(snapshot.typed, [('x', Outcome::Incorrect)]);
+        assert_eq!(snapshot.scores, None);
+
+        thok.write('b');
+        thok.calc_results();
+        publisher.publish(&Snapshot::of(&thok));
+        let watched = next(&watcher).to_thok();
+        assert_eq!(watched.prompt, "ab");
+        assert_eq!(watched.input.len(), 2);
+        assert!(watched.has_finished());
+        assert_eq!(watched.accuracy, thok.accuracy);
+    }
+
+    #[test]
+    fn only_changes_are_published() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut publisher = Publisher::new(listener);
+        let mut snapshot = Snapshot::of(&Thok::new(String::from("ab"), 1, None));
+        snapshot.elapsed_secs = Some(1.0);
+        publisher.publish(&snapshot);
+
+        snapshot.elapsed_secs = Some(2.0);
+        publisher.publish(&snapshot);
+        assert_eq!(publisher.last.as_ref().unwrap().elapsed_secs, Some(1.0));
+
+        snapshot.cursor = 1;
+        publisher.publish(&snapshot);
+        assert_eq!(publisher.last.as_ref(), Some(&snapshot));
+    }
+
+    #[test]
+    fn stalled_watchers_dont_hold_up_publishing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut publisher = Publisher::new(listener);
+        // connected, but never reads, so its socket buffers fill up
+        let _stalled = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        let mut snapshot = Snapshot::of(&Thok::new("a ".repeat(500_000), 500_000, None));
+        let started = Instant::now();
+        for cursor in 0..8 {
+            snapshot.cursor = cursor;
+            publisher.publish(&snapshot);
+        }
+        // writing to it here would time out at least once
+        assert!(started.elapsed() < WRITE_TIMEOUT, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn oversized_snapshots_are_refused() {
+        let mut flood = io::Cursor::new(vec![b'a'; MAX_SNAPSHOT_BYTES as usize + 1]);
+        let error = read_snapshot_line(&mut flood, &mut String::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut line = String::new();
+        let read = read_snapshot_line(&mut io::Cursor::new("{}\n"), &mut line).unwrap();
+        assert_eq!((read, line.as_str()), (3, "{}\n"));
+    }
+
+    #[test]
+    fn watchers_draw_the_same_screen() {
+        let mut thok = Thok::new(String::from("the quick brown fox"), 4, None);
+        for c in "the qx".chars() {
+            thok.write(c);
+        }
+
+        let area = Rect::new(0, 0, 40, 10);
+        let mut typed = Buffer::empty(area);
+        let mut watched = Buffer::empty(area);
+        (&thok).render(area, &mut typed);
+        (&Snapshot::of(&thok).to_thok()).render(area, &mut watched);
+        assert_eq!(typed, watched);
+    }
+
+    #[test]
+    fn snapshots_keep_the_clock_running() {
+        let snapshot = Snapshot {
+            prompt: String::from("the cat"),
+            typed: vec![('t', Outcome::Correct)],
+            cursor: 1,
+            number_of_secs: Some(30.0),
+            elapsed_secs: Some(10.0),
+            pace_wpm: None,
+            scores: None,
+        };
+        let thok = snapshot.to_thok();
+        assert!(!thok.has_finished());
+        let remaining = thok.seconds_remaining().unwrap();
+        assert!(remaining <= 20.0 && remaining > 19.0, "{}", remaining);
+    }
+}
@@ -1,68 +1,256 @@
+use crate::clock::{Clock, SystemClock};
+use crate::keyboard::{self, KeyboardLayout, KeyboardStats};
 use crate::util::std_dev;
 use crate::TICK_RATE_MS;
 use chrono::prelude::*;
-use directories::ProjectDirs;
 use itertools::Itertools;
-use std::fs::OpenOptions;
-use std::io::{self, Write};
-use std::{char, collections::HashMap, time::SystemTime};
-
-#[derive(Clone, Debug, Copy, PartialEq)]
+use serde::{Deserialize, Serialize};
+use std::{
+    char,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Outcome {
     Correct,
     Incorrect,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Input {
+pub(crate) struct Input {
     pub char: char,
     pub outcome: Outcome,
-    pub timestamp: SystemTime,
+    pub timestamp: Instant,
 }
 
-/// represents a test being displayed to the user
-#[derive(Debug)]
-pub struct Thok {
+/// What the results screen shows above the stats line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultsView {
+    Chart,
+    Keyboard,
+    Words,
+    Diff,
+}
+
+/// How a single word of the prompt was typed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordStat {
+    pub word: String,
+    /// prompt char indices of the word
+    pub range: Range<usize>,
+    /// None when the whole word was typed within the same instant
+    pub wpm: Option<f64>,
+    /// chars typed wrong, including ones fixed with backspace
+    pub errors: usize,
+    /// pause between the previous keystroke and the word's first char
+    pub hesitation_ms: f64,
+}
+
+/// The outcome of a test, e.g. for scripts (`thokr simulate`).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Results {
     pub prompt: String,
-    pub prompt_chars: Vec<char>,
-    pub input: Vec<Input>,
-    pub raw_coords: Vec<(f64, f64)>,
-    pub wpm_coords: Vec<(f64, f64)>,
-    pub cursor_pos: usize,
-    pub started_at: Option<SystemTime>,
-    pub seconds_remaining: Option<f64>,
-    pub number_of_secs: Option<f64>,
-    pub number_of_words: usize,
+    pub typed: String,
+    /// the whole prompt was typed or time ran out
+    pub finished: bool,
+    pub elapsed_secs: f64,
     pub wpm: f64,
+    /// wpm counting every char typed, right or wrong
+    pub raw_wpm: f64,
     pub accuracy: f64,
     pub std_dev: f64,
-    pub pace_wpm: Option<f64>,
+    pub correct: usize,
+    pub incorrect: usize,
+    /// mistakes that were backspaced over
+    pub corrected: usize,
+    /// (second, wpm) points of the results chart
+    pub wpm_coords: Vec<(f64, f64)>,
+    pub words: Vec<WordResult>,
+}
+
+/// How a single word was typed, see `WordStat`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WordResult {
+    pub word: String,
+    pub wpm: Option<f64>,
+    pub errors: usize,
+    pub hesitation_ms: f64,
+}
+
+/// represents a test being displayed to the user
+#[derive(Debug)]
+pub struct Thok {
+    pub(crate) prompt: String,
+    pub(crate) prompt_chars: Vec<char>,
+    pub(crate) input: Vec<Input>,
+    /// prompt indices where a mistake was backspaced over
+    pub(crate) corrected: HashSet<usize>,
+    pub(crate) wpm_coords: Vec<(f64, f64)>,
+    pub(crate) cursor_pos: usize,
+    pub(crate) started_at: Option<Instant>,
+    pub(crate) number_of_secs: Option<f64>,
+    pub(crate) number_of_words: usize,
+    pub(crate) wpm: f64,
+    pub(crate) accuracy: f64,
+    pub(crate) std_dev: f64,
+    pub(crate) pace_wpm: Option<f64>,
+    pub(crate) keyboard: KeyboardLayout,
+    pub(crate) keyboard_stats: Option<KeyboardStats>,
+    pub(crate) word_stats: Vec<WordStat>,
+    pub(crate) results_view: ResultsView,
+    /// wall-clock time the test finished, set by `calc_results`
+    pub(crate) finished_at: Option<DateTime<Local>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Thok {
     pub fn new(prompt: String, number_of_words: usize, number_of_secs: Option<f64>) -> Self {
+        Self::with_clock(
+            prompt,
+            number_of_words,
+            number_of_secs,
+            Arc::new(SystemClock),
+        )
+    }
+
+    /// A test timed by `clock` instead of the system clock.
+    pub fn with_clock(
+        prompt: String,
+        number_of_words: usize,
+        number_of_secs: Option<f64>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         let prompt_chars = prompt.chars().collect();
         Self {
             prompt,
             prompt_chars,
             input: vec![],
-            raw_coords: vec![],
+            corrected: HashSet::new(),
             wpm_coords: vec![],
             cursor_pos: 0,
             started_at: None,
             number_of_secs,
             number_of_words,
-            seconds_remaining: number_of_secs,
             wpm: 0.0,
             accuracy: 0.0,
             std_dev: 0.0,
             pace_wpm: None,
+            keyboard: KeyboardLayout::default(),
+            keyboard_stats: None,
+            word_stats: vec![],
+            results_view: ResultsView::Chart,
+            finished_at: None,
+            clock,
+        }
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn number_of_words(&self) -> usize {
+        self.number_of_words
+    }
+
+    /// Length of a timed test, None for untimed tests.
+    pub fn number_of_secs(&self) -> Option<f64> {
+        self.number_of_secs
+    }
+
+    /// Where the next keystroke goes, as a prompt char index.
+    pub fn cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    /// Net words per minute, set by `calc_results`.
+    pub fn wpm(&self) -> f64 {
+        self.wpm
+    }
+
+    /// Percentage of keystrokes that were correct, set by `calc_results`.
+    pub fn accuracy(&self) -> f64 {
+        self.accuracy
+    }
+
+    /// Standard deviation of the per-second wpm, set by `calc_results`.
+    pub fn std_dev(&self) -> f64 {
+        self.std_dev
+    }
+
+    /// (second, wpm) points of the results chart, set by `calc_results`.
+    pub fn wpm_coords(&self) -> &[(f64, f64)] {
+        &self.wpm_coords
+    }
+
+    /// When the test finished, set by `calc_results`.
+    pub fn finished_at(&self) -> Option<DateTime<Local>> {
+        self.finished_at
+    }
+
+    pub fn pace_wpm(&self) -> Option<f64> {
+        self.pace_wpm
+    }
+
+    /// Shows a ghost caret moving at `wpm`, or none.
+    pub fn set_pace_wpm(&mut self, wpm: Option<f64>) {
+        self.pace_wpm = wpm;
+    }
+
+    /// Layout the finger analytics and heatmap of the results are for.
+    pub fn set_keyboard(&mut self, keyboard: KeyboardLayout) {
+        self.keyboard = keyboard;
+    }
+
+    pub fn results_view(&self) -> ResultsView {
+        self.results_view
+    }
+
+    /// When a timed test ends, None for untimed tests or before the start.
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.started_at? + Duration::from_secs_f64(self.number_of_secs?))
+    }
+
+    /// Seconds since the first keystroke, None before the test starts. Timed
+    /// tests stop counting at their deadline.
+    pub fn elapsed_secs(&self) -> Option<f64> {
+        let started_at = self.started_at?;
+        let now = match self.deadline() {
+            Some(deadline) => self.clock.now().min(deadline),
+            None => self.clock.now(),
+        };
+        Some(now.saturating_duration_since(started_at).as_secs_f64())
+    }
+
+    /// Seconds left in a timed test, None for untimed tests.
+    pub fn seconds_remaining(&self) -> Option<f64> {
+        let number_of_secs = self.number_of_secs?;
+        Some(number_of_secs - self.elapsed_secs().unwrap_or(0.0))
+    }
+
+    /// How long until the screen of a running test changes by itself: the
+    /// countdown or pace caret moves every `TICK_RATE_MS` and the test ends
+    /// at its deadline. None when nothing changes until the next keystroke.
+    pub fn next_tick(&self) -> Option<Duration> {
+        if !self.has_started()
+            || self.has_finished()
+            || (self.number_of_secs.is_none() && self.pace_wpm.is_none())
+        {
+            return None;
         }
+        let tick = Duration::from_millis(TICK_RATE_MS);
+        Some(match self.deadline() {
+            Some(deadline) => tick.min(deadline.saturating_duration_since(self.clock.now())),
+            None => tick,
+        })
     }
 
     /// Index of the pace caret after `elapsed_secs`, or None if pacing is
     /// off or the pace caret has run past the end of the prompt.
-    pub fn pace_caret_index_at(&self, elapsed_secs: f64) -> Option<usize> {
+    pub(crate) fn pace_caret_index_at(&self, elapsed_secs: f64) -> Option<usize> {
         let wpm = self.pace_wpm?;
         let idx = (elapsed_secs * wpm * 5.0 / 60.0).floor() as usize;
         if idx >= self.char_count() {
@@ -74,38 +262,31 @@ impl Thok {
 
     /// Pace caret position now; None before the test starts.
     pub fn pace_caret_index(&self) -> Option<usize> {
-        let started_at = self.started_at?;
-        let elapsed = started_at.elapsed().ok()?.as_secs_f64();
-        self.pace_caret_index_at(elapsed)
-    }
-
-    pub fn on_tick(&mut self) {
-        if let Some(remaining) = self.seconds_remaining {
-            self.seconds_remaining = Some(remaining - (TICK_RATE_MS as f64 / 1000_f64));
-        }
+        self.pace_caret_index_at(self.elapsed_secs()?)
     }
 
     pub fn char_count(&self) -> usize {
         self.prompt_chars.len()
     }
 
-    pub fn get_expected_char(&self, idx: usize) -> char {
+    pub(crate) fn get_expected_char(&self, idx: usize) -> char {
         self.prompt_chars[idx]
     }
 
-    pub fn increment_cursor(&mut self) {
+    fn increment_cursor(&mut self) {
         if self.cursor_pos < self.input.len() {
             self.cursor_pos += 1;
         }
     }
 
-    pub fn decrement_cursor(&mut self) {
+    fn decrement_cursor(&mut self) {
         if self.cursor_pos > 0 {
             self.cursor_pos -= 1;
         }
     }
 
     pub fn calc_results(&mut self) {
+        self.finished_at.get_or_insert_with(Local::now);
         let correct_chars = self
             .input
             .clone()
@@ -113,7 +294,7 @@ impl Thok {
             .filter(|i| i.outcome == Outcome::Correct)
             .collect::<Vec<Input>>();
 
-        let elapsed_secs = self.started_at.unwrap().elapsed().unwrap().as_secs_f64();
+        let elapsed_secs = self.elapsed_secs().unwrap();
 
         let whole_second_limit = elapsed_secs.floor();
 
@@ -123,8 +304,7 @@ impl Thok {
             .fold(HashMap::new(), |mut map, i| {
                 let mut num_secs = i
                     .timestamp
-                    .duration_since(self.started_at.unwrap())
-                    .unwrap()
+                    .saturating_duration_since(self.started_at.unwrap())
                     .as_secs_f64();
 
                 if num_secs == 0.0 {
@@ -179,17 +359,37 @@ impl Thok {
         } else {
             ((correct_chars.len() as f64 / self.input.len() as f64) * 100.0).round()
         };
+
+        self.keyboard_stats = Some(keyboard::analyze(
+            &self.keyboard,
+            &self.prompt_chars,
+            &self.input,
+        ));
+        self.word_stats = self.word_stats();
+    }
+
+    /// Switches the results screen to `view`, or back to the chart if it's
+    /// already showing.
+    pub fn toggle_results_view(&mut self, view: ResultsView) {
+        self.results_view = if self.results_view == view {
+            ResultsView::Chart
+        } else {
+            view
+        };
     }
 
     pub fn backspace(&mut self) {
         if self.cursor_pos > 0 {
-            self.input.remove(self.cursor_pos - 1);
+            let removed = self.input.remove(self.cursor_pos - 1);
+            if removed.outcome == Outcome::Incorrect {
+                self.corrected.insert(self.cursor_pos - 1);
+            }
             self.decrement_cursor();
         }
     }
 
-    pub fn start(&mut self) {
-        self.started_at = Some(SystemTime::now());
+    fn start(&mut self) {
+        self.started_at = Some(self.clock.now());
     }
 
     pub fn write(&mut self, c: char) {
@@ -209,7 +409,7 @@ impl Thok {
             Input {
                 char: c,
                 outcome,
-                timestamp: SystemTime::now(),
+                timestamp: self.clock.now(),
             },
         );
         self.increment_cursor();
@@ -219,64 +419,147 @@ impl Thok {
         self.started_at.is_some()
     }
 
-    pub fn has_finished(&self) -> bool {
-        (self.input.len() == self.char_count())
-            || (self.seconds_remaining.is_some() && self.seconds_remaining.unwrap() <= 0.0)
-    }
+    /// Prompt char ranges of the words that have been fully typed.
+    fn typed_words(&self) -> Vec<Range<usize>> {
+        let mut words = vec![];
+        let mut start = None;
 
-    pub fn save_results(&self) -> io::Result<()> {
-        if let Some(proj_dirs) = ProjectDirs::from("", "", "thokr") {
-            let config_dir = proj_dirs.config_dir();
-            let log_path = config_dir.join("log.csv");
+        for idx in 0..=self.char_count() {
+            let is_space = self.prompt_chars.get(idx).is_none_or(|c| c.is_whitespace());
+            match (start, is_space) {
+                (None, false) => start = Some(idx),
+                (Some(s), true) => {
+                    // the word counts once its last char has been typed
+                    if idx <= self.input.len() {
+                        words.push(s..idx);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
 
-            std::fs::create_dir_all(config_dir)?;
+        words
+    }
 
-            // If the config file doesn't exist, we need to emit a header
-            let needs_header = !log_path.exists();
+    /// Timing and errors of each fully typed word. Errors include the space
+    /// after the word and mistakes that were backspaced over; the time of a
+    /// word runs from the keystroke before it, so it includes the hesitation
+    /// before its first char.
+    pub fn word_stats(&self) -> Vec<WordStat> {
+        let ms_between =
+            |a: Instant, b: Instant| b.saturating_duration_since(a).as_secs_f64() * 1000.0;
 
-            let mut log_file = OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(log_path)?;
+        self.typed_words()
+            .into_iter()
+            .map(|range| {
+                let end = (range.end + 1).min(self.input.len());
+                let errors = (range.start..end)
+                    .filter(|i| {
+                        self.input[*i].outcome == Outcome::Incorrect || self.corrected.contains(i)
+                    })
+                    .count();
+
+                let first = self.input[range.start].timestamp;
+                let last = self.input[range.end - 1].timestamp;
+                let (hesitation_ms, elapsed_ms) = match range.start {
+                    0 => (0.0, ms_between(first, last)),
+                    s => {
+                        let before = self.input[s - 1].timestamp;
+                        (ms_between(before, first), ms_between(before, last))
+                    }
+                };
+
+                WordStat {
+                    word: self.prompt_chars[range.clone()].iter().collect(),
+                    wpm: (elapsed_ms > 0.0)
+                        .then(|| (range.len() as f64 / 5.0) / (elapsed_ms / 60_000.0)),
+                    range,
+                    errors,
+                    hesitation_ms,
+                }
+            })
+            .collect()
+    }
 
-            if needs_header {
-                writeln!(
-                    log_file,
-                    "date,num_words,num_secs,elapsed_secs,wpm,accuracy,std_dev"
-                )?;
-            }
+    /// Each fully typed word of the prompt and whether any of its chars (or
+    /// the space after it) was typed incorrectly, even if fixed afterwards.
+    #[doc(hidden)]
+    pub fn word_outcomes(&self) -> Vec<(String, bool)> {
+        self.word_stats()
+            .into_iter()
+            .map(|w| (w.word, w.errors > 0))
+            .collect()
+    }
 
-            let elapsed_secs = self.started_at.unwrap().elapsed().unwrap().as_secs_f64();
-
-            writeln!(
-                log_file,
-                "{},{},{},{:.2},{},{},{:.2}",
-                Local::now().format("%c"),
-                self.number_of_words,
-                self.number_of_secs
-                    .map_or(String::from(""), |ns| format!("{:.2}", ns)),
-                elapsed_secs,
-                self.wpm,      // already rounded, no need to round to two decimal places
-                self.accuracy, // already rounded, no need to round to two decimal places
-                self.std_dev,
-            )?;
-        }
+    /// Whether the whole prompt has been typed, rather than the time having
+    /// run out.
+    pub fn completed_prompt(&self) -> bool {
+        self.input.len() == self.char_count()
+    }
 
-        Ok(())
+    pub fn has_finished(&self) -> bool {
+        self.completed_prompt()
+            || self
+                .seconds_remaining()
+                .is_some_and(|remaining| remaining <= 0.0)
+    }
+
+    /// Results as of now; wpm and the like are only filled in by
+    /// `calc_results`.
+    pub fn results(&self) -> Results {
+        let count = |outcome| self.input.iter().filter(|i| i.outcome == outcome).count();
+        let elapsed_secs = self.elapsed_secs().unwrap_or(0.0);
+        Results {
+            prompt: self.prompt.clone(),
+            typed: self.input.iter().map(|i| i.char).collect(),
+            finished: self.has_finished(),
+            elapsed_secs,
+            wpm: self.wpm,
+            raw_wpm: if elapsed_secs > 0.0 {
+                (self.input.len() as f64 / 5.0 * 60.0 / elapsed_secs).ceil()
+            } else {
+                0.0
+            },
+            accuracy: self.accuracy,
+            std_dev: self.std_dev,
+            correct: count(Outcome::Correct),
+            incorrect: count(Outcome::Incorrect),
+            corrected: self.corrected.len(),
+            wpm_coords: self.wpm_coords.clone(),
+            words: self
+                .word_stats()
+                .into_iter()
+                .map(|w| WordResult {
+                    word: w.word,
+                    wpm: w.wpm,
+                    errors: w.errors,
+                    hesitation_ms: w.hesitation_ms,
+                })
+                .collect(),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::{Duration, SystemTime};
+    use crate::clock::FakeClock;
 
-    /// Builds a finished-by-length thok: every char of `typed` written against
-    /// `prompt`, with the i-th keystroke stamped at started_at + offsets[i].
+    /// Builds a thok on a fake clock: every char of `typed` written against
+    /// `prompt`, with the i-th keystroke stamped at started_at + offsets[i],
+    /// and the clock left a minute after the start.
     fn thok_with_input(prompt: &str, typed: &str, offsets_ms: &[u64]) -> Thok {
-        let mut thok = Thok::new(prompt.to_string(), prompt.split(' ').count(), None);
-        let started_at = SystemTime::now() - Duration::from_secs(60);
+        let clock = FakeClock::new();
+        let mut thok = Thok::with_clock(
+            prompt.to_string(),
+            prompt.split(' ').count(),
+            None,
+            Arc::new(clock.clone()),
+        );
+        let started_at = clock.now();
         thok.started_at = Some(started_at);
+        clock.set(Duration::from_secs(60));
         for (i, c) in typed.chars().enumerate() {
             let outcome = if c == prompt.chars().nth(i).unwrap() {
                 Outcome::Correct
@@ -319,6 +602,19 @@ mod tests {
         assert_eq!(empty.cursor_pos, 0);
     }
 
+    #[test]
+    fn backspacing_a_mistake_marks_it_corrected() {
+        let mut thok = Thok::new("abc".to_string(), 1, None);
+        thok.write('a');
+        thok.backspace();
+        thok.write('a');
+        thok.write('x');
+        thok.backspace();
+        thok.write('b');
+        assert_eq!(thok.corrected, HashSet::from([1]));
+        assert!(thok.input.iter().all(|i| i.outcome == Outcome::Correct));
+    }
+
     #[test]
     fn has_finished_by_length() {
         let mut thok = Thok::new("ab".to_string(), 1, None);
@@ -330,14 +626,35 @@ mod tests {
 
     #[test]
     fn has_finished_by_timer() {
-        let mut thok = Thok::new("abc".to_string(), 1, Some(0.3));
+        let clock = FakeClock::new();
+        let mut thok = Thok::with_clock("abc".to_string(), 1, Some(0.3), Arc::new(clock.clone()));
+        assert_eq!(thok.seconds_remaining(), Some(0.3));
         thok.write('a');
+        clock.advance(Duration::from_millis(299));
         assert!(!thok.has_finished());
-        // each tick subtracts TICK_RATE_MS (100ms = 0.1s)
-        thok.on_tick();
-        thok.on_tick();
-        thok.on_tick();
+        clock.advance(Duration::from_millis(1));
         assert!(thok.has_finished());
+        assert_eq!(thok.seconds_remaining(), Some(0.0));
+    }
+
+    #[test]
+    fn timed_tests_end_at_their_deadline() {
+        let clock = FakeClock::new();
+        let mut thok =
+            Thok::with_clock("abcdef".to_string(), 1, Some(1.0), Arc::new(clock.clone()));
+        assert_eq!(thok.next_tick(), None);
+        thok.write('a');
+        assert_eq!(thok.next_tick(), Some(Duration::from_millis(TICK_RATE_MS)));
+
+        clock.advance(Duration::from_millis(950));
+        assert_eq!(thok.next_tick(), Some(Duration::from_millis(50)));
+
+        // results are computed as of the deadline however late they come
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(thok.next_tick(), None);
+        assert_eq!(thok.elapsed_secs(), Some(1.0));
+        thok.calc_results();
+        assert_eq!(thok.wpm_coords.last().unwrap().0, 1.0);
     }
 
     #[test]
@@ -425,18 +742,103 @@ mod tests {
     }
 
     #[test]
-    fn tick_without_timer_is_noop() {
+    fn untimed_tests_never_tick() {
         let mut thok = Thok::new("abc".to_string(), 1, None);
         assert_eq!(thok.number_of_secs, None);
-        thok.on_tick();
-        assert_eq!(thok.seconds_remaining, None);
+        thok.write('a');
+        assert_eq!(thok.next_tick(), None);
+        assert_eq!(thok.seconds_remaining(), None);
         assert!(!thok.has_finished());
     }
 
+    #[test]
+    fn word_outcomes_align_with_word_boundaries() {
+        let thok = thok_with_input("the cat sat", "the cxt sa", &[0; 10]);
+        assert_eq!(
+            thok.word_outcomes(),
+            vec![("the".to_string(), false), ("cat".to_string(), true)]
+        );
+
+        // a wrong char in place of the space counts against the word before it
+        let thok = thok_with_input("ab cd", "abxcd", &[0; 5]);
+        assert_eq!(
+            thok.word_outcomes(),
+            vec![("ab".to_string(), true), ("cd".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn word_stats_time_each_word() {
+        // "ab" typed over 500ms, then a 1s pause before "cd" (typed in 200ms)
+        let thok = thok_with_input("ab cd", "ab cx", &[0, 500, 600, 1600, 1800]);
+        let stats = thok.word_stats();
+        assert_eq!(stats.len(), 2);
+
+        assert_eq!(stats[0].word, "ab");
+        assert_eq!(stats[0].hesitation_ms, 0.0);
+        // 2 chars in 0.5s => (2/5) / (0.5/60)
+        assert_eq!(stats[0].wpm.unwrap().round(), 48.0);
+        assert_eq!(stats[0].errors, 0);
+
+        assert_eq!(stats[1].range, 3..5);
+        assert_eq!(stats[1].hesitation_ms.round(), 1000.0);
+        // 2 chars in 1.2s including the hesitation
+        assert_eq!(stats[1].wpm.unwrap().round(), 20.0);
+        assert_eq!(stats[1].errors, 1);
+    }
+
+    #[test]
+    fn fixed_mistakes_still_count() {
+        let mut thok = Thok::new("ab cd".to_string(), 2, None);
+        for c in "ax".chars() {
+            thok.write(c);
+        }
+        thok.backspace();
+        for c in "b cd".chars() {
+            thok.write(c);
+        }
+        assert_eq!(thok.word_stats()[0].errors, 1);
+        assert_eq!(
+            thok.word_outcomes(),
+            vec![("ab".to_string(), true), ("cd".to_string(), false)]
+        );
+
+        let mut review = crate::review::ReviewStore::default();
+        review.record(&thok.word_outcomes(), crate::review::today());
+        assert!(review.words.contains_key("ab"));
+    }
+
+    #[test]
+    fn timing_follows_the_clock() {
+        let clock = FakeClock::new();
+        let mut thok = Thok::with_clock("ab cd".to_string(), 2, None, Arc::new(clock.clone()));
+        thok.pace_wpm = Some(60.0);
+
+        clock.set(Duration::from_secs(5));
+        thok.write('a');
+        assert_eq!(thok.elapsed_secs(), Some(0.0));
+        for (ms, c) in [(500, 'b'), (1000, ' '), (1500, 'c'), (2000, 'd')] {
+            clock.set(Duration::from_millis(5000 + ms));
+            thok.write(c);
+        }
+        assert_eq!(thok.elapsed_secs(), Some(2.0));
+        // 60 wpm is 5 chars a second
+        assert_eq!(thok.pace_caret_index(), None);
+        assert_eq!(thok.pace_caret_index_at(0.5), Some(2));
+
+        thok.calc_results();
+        // 5 chars in 2s => (5/5) / (2/60)
+        assert_eq!(thok.wpm, 30.0);
+        assert_eq!(thok.wpm_coords, vec![(1.0, 36.0), (2.0, 30.0)]);
+        assert_eq!(thok.word_stats()[1].hesitation_ms, 500.0);
+    }
+
     #[test]
     fn calc_results_empty_input_does_not_panic() {
-        let mut thok = Thok::new("hello".to_string(), 1, None);
-        thok.started_at = Some(SystemTime::now() - Duration::from_secs(60));
+        let clock = FakeClock::new();
+        let mut thok = Thok::with_clock("hello".to_string(), 1, None, Arc::new(clock.clone()));
+        thok.start();
+        clock.set(Duration::from_secs(60));
         thok.calc_results();
         assert_eq!(thok.wpm, 0.0);
         assert_eq!(thok.std_dev, 0.0);
@@ -5,13 +5,15 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Axis, Chart, Dataset, GraphType, Paragraph, Widget},
 };
-use webbrowser::Browser;
 
+use crate::keyboard::{KeyPos, KeyboardLayout, KeyboardStats, SPACE_ROW};
 use crate::layout;
-use crate::thok::{Outcome, Thok};
+use crate::thok::{Outcome, ResultsView, Thok, WordStat};
 
-const HORIZONTAL_MARGIN: u16 = 5;
-const VERTICAL_MARGIN: u16 = 2;
+/// blank columns left and right of the prompt and the results
+pub const HORIZONTAL_MARGIN: u16 = 5;
+/// blank lines above and below the results
+pub const VERTICAL_MARGIN: u16 = 2;
 
 /// Shared geometry for the running view, so the renderer and the hardware
 /// cursor math cannot drift. Returns the per-line max width, the wrapped
@@ -57,6 +59,13 @@ fn running_geometry(thok: &Thok, area: Rect) -> RunningGeometry {
 /// Screen cell for the hardware cursor while a test is running.
 /// `None` when the test has finished (the results screen has no cursor).
 pub fn cursor_screen_position(thok: &Thok, area: Rect) -> Option<Position> {
+    char_screen_position(thok, area, thok.cursor_pos)
+}
+
+/// Screen cell of the prompt char at `idx` while a test is running, e.g. to
+/// mark where someone else typing the same prompt is. `None` when the test
+/// has finished or `idx` is past the prompt.
+pub fn char_screen_position(thok: &Thok, area: Rect, idx: usize) -> Option<Position> {
     if thok.has_finished() {
         return None;
     }
@@ -64,14 +73,14 @@ pub fn cursor_screen_position(thok: &Thok, area: Rect) -> Option<Position> {
     let geo = running_geometry(thok, area);
     let prompt_chunk = geo.chunks[2];
 
-    let (line_no, col) =
-        layout::char_cell(&thok.prompt_chars, geo.max_chars_per_line, thok.cursor_pos)?;
+    let (line_no, col) = layout::char_cell(&thok.prompt_chars, geo.max_chars_per_line, idx)?;
 
     let line_len = geo.lines.get(line_no).map(|r| r.end - r.start).unwrap_or(0) as u16;
 
-    // alignment matches the renderer: center only when the prompt is one line
+    // alignment matches the renderer: center only when the prompt is one line,
+    // rounding the way `Paragraph` does
     let x_offset = if geo.lines.len() == 1 {
-        (prompt_chunk.width.saturating_sub(line_len)) / 2
+        (prompt_chunk.width / 2).saturating_sub(line_len / 2)
     } else {
         0
     };
@@ -81,6 +90,325 @@ pub fn cursor_screen_position(thok: &Thok, area: Rect) -> Option<Position> {
     Some(Position::new(x, y))
 }
 
+/// The line the timer of a running test goes on, two above the prompt. It's
+/// there for untimed tests too, for a caller to show its own status in.
+pub fn timer_area(thok: &Thok, area: Rect) -> Rect {
+    let prompt_chunk = running_geometry(thok, area).chunks[2];
+    Rect {
+        y: prompt_chunk.y.saturating_sub(2),
+        height: 1.min(prompt_chunk.y),
+        ..prompt_chunk
+    }
+}
+
+/// `parts` joined by `sep` into lines of at most `width` chars, breaking
+/// between parts only; a line that breaks keeps the visible end of `sep`, so
+/// "a / b" becomes "a /" and "b". Parts wider than `width` get a line of
+/// their own.
+pub fn wrap_parts<S: AsRef<str>>(parts: &[S], sep: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for part in parts.iter().map(AsRef::as_ref) {
+        if line.is_empty() {
+            line.push_str(part);
+        } else if line.chars().count() + sep.chars().count() + part.chars().count() <= width {
+            line.push_str(sep);
+            line.push_str(part);
+        } else {
+            line.push_str(sep.trim_end());
+            lines.push(std::mem::replace(&mut line, part.to_string()));
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// cells per key in the heatmap, including the gap to the next key
+const KEY_PITCH: u16 = 4;
+/// horizontal stagger of each heatmap row, in cells
+const ROW_OFFSETS: [u16; 4] = [0, 6, 7, 9];
+
+/// Heatmap of the keyboard colored by error rate per key, with per-finger
+/// timing and bigram stats underneath.
+fn render_keyboard(layout: &KeyboardLayout, stats: &KeyboardStats, area: Rect, buf: &mut Buffer) {
+    let bold_style = Style::default().add_modifier(Modifier::BOLD);
+    let dim_style = Style::default().add_modifier(Modifier::DIM);
+
+    let key_style = |pos: KeyPos| match stats.keys.get(&pos) {
+        Some(count) if count.hits > 0 => {
+            let error_rate = count.errors as f64 / count.hits as f64;
+            let color = if count.errors == 0 {
+                Color::Green
+            } else if error_rate <= 0.1 {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            Style::default()
+                .patch(bold_style)
+                .fg(Color::Black)
+                .bg(color)
+        }
+        _ => dim_style,
+    };
+
+    let mut lines = layout
+        .rows()
+        .enumerate()
+        .map(|(row, keys)| {
+            let mut spans = vec![Span::raw(" ".repeat(ROW_OFFSETS[row] as usize))];
+            for (pos, c) in keys {
+                spans.push(Span::styled(format!(" {} ", c), key_style(pos)));
+                spans.push(Span::raw(" "));
+            }
+            Line::from(spans)
+        })
+        .collect::<Vec<Line>>();
+
+    let space = KeyPos {
+        row: SPACE_ROW,
+        col: 0,
+    };
+    lines.push(Line::from(vec![
+        Span::raw(" ".repeat((ROW_OFFSETS[3] + KEY_PITCH * 3) as usize)),
+        Span::styled(format!("{:^23}", "space"), key_style(space)),
+    ]));
+
+    let block_width = layout
+        .rows()
+        .enumerate()
+        .map(|(row, keys)| ROW_OFFSETS[row] + keys.len() as u16 * KEY_PITCH)
+        .max()
+        .unwrap_or(0);
+
+    let fingers = stats
+        .fingers
+        .iter()
+        .filter_map(|(finger, count)| {
+            count
+                .avg_ms()
+                .map(|ms| format!("{} {:.0}ms", finger.short_name(), ms))
+        })
+        .collect::<Vec<String>>()
+        .join("  ");
+
+    let bigrams = format!(
+        "{}   same-finger {}%   alternation {}%",
+        layout.name,
+        stats.same_finger_pct(),
+        stats.alternation_pct()
+    );
+
+    let height = lines.len() as u16 + 3;
+    let top = area.y + area.height.saturating_sub(height) / 2;
+    let left = area.x + area.width.saturating_sub(block_width) / 2;
+
+    Paragraph::new(lines).render(
+        Rect::new(
+            left,
+            top,
+            area.width - (left - area.x),
+            area.height - (top - area.y),
+        ),
+        buf,
+    );
+
+    let summary_area = Rect::new(
+        area.x,
+        (top + height - 2).min(area.bottom().saturating_sub(2)),
+        area.width,
+        2.min(area.height),
+    );
+    Paragraph::new(vec![
+        Line::from(Span::styled(fingers, bold_style)),
+        Line::from(Span::styled(bigrams, dim_style)),
+    ])
+    .alignment(Alignment::Center)
+    .render(summary_area, buf);
+}
+
+/// number of words listed as slowest / most error-prone
+const WORST_WORDS: usize = 3;
+
+/// The prompt with the slowest words highlighted in yellow and the most
+/// error-prone ones in red, followed by a list of both.
+fn render_words(thok: &Thok, area: Rect, buf: &mut Buffer) {
+    let bold_style = Style::default().add_modifier(Modifier::BOLD);
+    let dim_style = Style::default().add_modifier(Modifier::DIM);
+
+    let mut slowest = thok
+        .word_stats
+        .iter()
+        .filter(|w| w.wpm.is_some())
+        .collect::<Vec<&WordStat>>();
+    slowest.sort_by(|a, b| a.wpm.partial_cmp(&b.wpm).unwrap());
+    slowest.truncate(WORST_WORDS);
+
+    let mut error_prone = thok
+        .word_stats
+        .iter()
+        .filter(|w| w.errors > 0)
+        .collect::<Vec<&WordStat>>();
+    error_prone.sort_by_key(|w| std::cmp::Reverse(w.errors));
+    error_prone.truncate(WORST_WORDS);
+
+    let char_style = |idx: usize| {
+        if error_prone.iter().any(|w| w.range.contains(&idx)) {
+            Style::default().patch(bold_style).fg(Color::Red)
+        } else if slowest.iter().any(|w| w.range.contains(&idx)) {
+            Style::default().patch(bold_style).fg(Color::Yellow)
+        } else {
+            dim_style
+        }
+    };
+
+    let width = area.width.max(1);
+    let prompt_lines = layout::wrap_chars(&thok.prompt_chars, width)
+        .into_iter()
+        .map(|r| {
+            Line::from(
+                r.map(|idx| Span::styled(thok.prompt_chars[idx].to_string(), char_style(idx)))
+                    .collect::<Vec<Span>>(),
+            )
+        })
+        .collect::<Vec<Line>>();
+
+    let slowest_list = slowest
+        .iter()
+        .map(|w| {
+            format!(
+                "{} {:.0} wpm ({:.0}ms pause)",
+                w.word,
+                w.wpm.unwrap_or(0.0),
+                w.hesitation_ms
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("   ");
+    let error_list = error_prone
+        .iter()
+        .map(|w| format!("{} {}x", w.word, w.errors))
+        .collect::<Vec<String>>()
+        .join("   ");
+
+    let height = prompt_lines.len() as u16 + 3;
+    let top = area.y + area.height.saturating_sub(height) / 2;
+    let prompt_area = Rect::new(
+        area.x,
+        top,
+        area.width,
+        (prompt_lines.len() as u16).min(area.bottom() - top),
+    );
+
+    Paragraph::new(prompt_lines)
+        .alignment(if prompt_area.height == 1 {
+            Alignment::Center
+        } else {
+            Alignment::Left
+        })
+        .render(prompt_area, buf);
+
+    let lists_area = Rect::new(
+        area.x,
+        (prompt_area.bottom() + 1).min(area.bottom().saturating_sub(2)),
+        area.width,
+        2.min(area.height),
+    );
+    Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("slowest   ", dim_style),
+            Span::styled(slowest_list, Style::default().fg(Color::Yellow)),
+        ]),
+        Line::from(vec![
+            Span::styled("errors   ", dim_style),
+            Span::styled(error_list, Style::default().fg(Color::Red)),
+        ]),
+    ])
+    .alignment(Alignment::Center)
+    .render(lists_area, buf);
+}
+
+/// The prompt as it was typed: each prompt line is drawn under a line holding
+/// the wrong chars that were typed in place of the expected ones. Corrected
+/// mistakes are underlined in yellow.
+fn render_diff(thok: &Thok, area: Rect, buf: &mut Buffer) {
+    let bold_style = Style::default().add_modifier(Modifier::BOLD);
+    let dim_style = Style::default().add_modifier(Modifier::DIM);
+    let green_bold_style = Style::default().patch(bold_style).fg(Color::Green);
+    let red_bold_style = Style::default().patch(bold_style).fg(Color::Red);
+    let corrected_style = Style::default()
+        .patch(bold_style)
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::UNDERLINED);
+
+    let visible = |c: char| if c == ' ' { '·' } else { c };
+
+    let mut lines = vec![];
+    for range in layout::wrap_chars(&thok.prompt_chars, area.width.max(1)) {
+        let mut typed = vec![];
+        let mut expected = vec![];
+
+        for idx in range {
+            let c = thok.prompt_chars[idx];
+            match thok.input.get(idx) {
+                Some(input) if input.outcome == Outcome::Incorrect => {
+                    typed.push(Span::styled(
+                        visible(input.char).to_string(),
+                        red_bold_style,
+                    ));
+                    expected.push(Span::styled(
+                        visible(c).to_string(),
+                        red_bold_style.add_modifier(Modifier::UNDERLINED),
+                    ));
+                }
+                Some(_) => {
+                    typed.push(Span::raw(" "));
+                    expected.push(Span::styled(
+                        c.to_string(),
+                        if thok.corrected.contains(&idx) {
+                            corrected_style
+                        } else {
+                            green_bold_style
+                        },
+                    ));
+                }
+                None => {
+                    typed.push(Span::raw(" "));
+                    expected.push(Span::styled(c.to_string(), dim_style));
+                }
+            }
+        }
+
+        lines.push(Line::from(typed));
+        lines.push(Line::from(expected));
+    }
+
+    let height = lines.len() as u16 + 2;
+    let top = area.y + area.height.saturating_sub(height) / 2;
+    let text_area = Rect::new(
+        area.x,
+        top,
+        area.width,
+        (lines.len() as u16).min(area.bottom() - top),
+    );
+
+    Paragraph::new(lines).render(text_area, buf);
+
+    let key_area = Rect::new(
+        area.x,
+        (text_area.bottom() + 1).min(area.bottom().saturating_sub(1)),
+        area.width,
+        1.min(area.height),
+    );
+    Paragraph::new(Line::from(vec![
+        Span::styled("mistyped", red_bold_style),
+        Span::styled(" (typed above)   ", dim_style),
+        Span::styled("corrected", corrected_style),
+    ]))
+    .alignment(Alignment::Center)
+    .render(key_area, buf);
+}
+
 impl Widget for &Thok {
     fn render(self, area: Rect, buf: &mut Buffer) {
         // styles
@@ -93,8 +421,6 @@ impl Widget for &Thok {
             .patch(bold_style)
             .add_modifier(Modifier::DIM);
 
-        let italic_style = Style::default().add_modifier(Modifier::ITALIC);
-
         let magenta_style = Style::default().fg(Color::Magenta);
 
         match !self.has_finished() {
@@ -155,26 +481,32 @@ impl Widget for &Thok {
 
                 widget.render(chunks[2], buf);
 
-                if let Some(sr) = self.seconds_remaining {
-                    let timer = Paragraph::new(Span::styled(format!("{:.1}", sr), dim_bold_style))
+                if let Some(text) = self.seconds_remaining().map(|sr| format!("{:.1}", sr)) {
+                    let timer = Paragraph::new(Span::styled(text, dim_bold_style))
                         .alignment(Alignment::Center);
 
                     timer.render(chunks[1], buf);
                 }
             }
             false => {
+                // the stats wrap rather than get cut off on narrow terminals
+                let width = area.width.saturating_sub(2 * HORIZONTAL_MARGIN) as usize;
+                let stats = wrap_parts(
+                    &[
+                        format!("{} wpm", self.wpm),
+                        format!("{}% acc", self.accuracy),
+                        format!("{:.2} sd", self.std_dev),
+                    ],
+                    "   ",
+                    width,
+                );
+
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
                     .horizontal_margin(HORIZONTAL_MARGIN)
                     .vertical_margin(VERTICAL_MARGIN)
                     .constraints(
-                        [
-                            Constraint::Min(1),
-                            Constraint::Length(1),
-                            Constraint::Length(1), // for padding
-                            Constraint::Length(1),
-                        ]
-                        .as_ref(),
+                        [Constraint::Min(1), Constraint::Length(stats.len() as u16)].as_ref(),
                     )
                     .split(area);
 
@@ -194,7 +526,7 @@ impl Widget for &Thok {
 
                 let mut overall_duration = match self.wpm_coords.last() {
                     Some(x) => x.0,
-                    _ => self.seconds_remaining.unwrap_or(1.0),
+                    _ => self.seconds_remaining().unwrap_or(1.0),
                 };
 
                 overall_duration = if overall_duration < 1.0 {
@@ -223,29 +555,20 @@ impl Widget for &Thok {
                             ]),
                     );
 
-                chart.render(chunks[0], buf);
+                match (self.results_view, &self.keyboard_stats) {
+                    (ResultsView::Keyboard, Some(stats)) => {
+                        render_keyboard(&self.keyboard, stats, chunks[0], buf)
+                    }
+                    (ResultsView::Words, _) => render_words(self, chunks[0], buf),
+                    (ResultsView::Diff, _) => render_diff(self, chunks[0], buf),
+                    _ => chart.render(chunks[0], buf),
+                }
 
-                let stats = Paragraph::new(Span::styled(
-                    format!(
-                        "{} wpm   {}% acc   {:.2} sd",
-                        self.wpm, self.accuracy, self.std_dev
-                    ),
-                    bold_style,
-                ))
-                .alignment(Alignment::Center);
+                let stats = Paragraph::new(stats.into_iter().map(Line::from).collect::<Vec<_>>())
+                    .style(bold_style)
+                    .alignment(Alignment::Center);
 
                 stats.render(chunks[1], buf);
-
-                let legend = Paragraph::new(Span::styled(
-                    String::from(if Browser::is_available() {
-                        "(r)etry / (n)ew / (t)weet / (esc)ape"
-                    } else {
-                        "(r)etry / (n)ew / (esc)ape"
-                    }),
-                    italic_style,
-                ));
-
-                legend.render(chunks[3], buf);
             }
         }
     }